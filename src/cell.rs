@@ -0,0 +1,334 @@
+//! The storage behind every lazy static.
+
+use core::cell::UnsafeCell;
+use core::convert::Infallible;
+use core::mem::MaybeUninit;
+#[cfg(all(feature = "std", not(feature = "critical-section")))]
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use crate::wait::{self, WaitStrategy};
+use crate::Site;
+
+/// Nobody has started evaluating the value yet.
+const UNINIT: u8 = 0;
+/// Some thread won the race and is running the initializer.
+const RUNNING: u8 = 1;
+/// The value is written and visible to every thread.
+const COMPLETE: u8 = 2;
+/// The initializer panicked. Stays like this until `clear_poison`.
+const POISONED: u8 = 3;
+
+/// A cell that is written exactly once, the first time it is needed.
+///
+/// The state goes `UNINIT -> RUNNING -> COMPLETE`. The thread that moves it to
+/// `RUNNING` (with a CAS) is the only one that runs the initializer, and it
+/// publishes the value with a `Release` store that readers pair with an
+/// `Acquire` load. If the initializer panics, the state becomes `POISONED`
+/// instead.
+pub struct LazyCell<T> {
+	state: AtomicU8,
+	/// The thread running the initializer (see `thread::current`), or zero.
+	/// Used to notice an initializer that needs its own value.
+	#[cfg(all(feature = "std", not(feature = "critical-section")))]
+	owner: AtomicUsize,
+	value: UnsafeCell<MaybeUninit<T>>,
+}
+
+// The value is shared between threads once it's written, and it may be
+// written by a different thread than the one that reads it.
+unsafe impl<T: Send + Sync> Sync for LazyCell<T> {}
+unsafe impl<T: Send> Send for LazyCell<T> {}
+
+impl<T> LazyCell<T> {
+	/// Creates an empty cell.
+	#[inline]
+	pub const fn new() -> Self {
+		Self {
+			state: AtomicU8::new(UNINIT),
+			#[cfg(all(feature = "std", not(feature = "critical-section")))]
+			owner: AtomicUsize::new(0),
+			value: UnsafeCell::new(MaybeUninit::uninit()),
+		}
+	}
+
+	/// Returns the value, running `f` first if nobody has done it yet.
+	///
+	/// `f` runs at most once, even if several threads get here at the same time.
+	/// The ones that lose the race wait for it using `W`.
+	///
+	/// # Panics
+	///
+	/// If the cell is poisoned, or `f` needs the value itself. `site` is only
+	/// used for the message.
+	#[inline]
+	pub fn get_or_init<W: WaitStrategy>(&self, site: &'static Site, f: impl FnOnce() -> T) -> &T {
+		match self.get_or_try_init::<W, Infallible>(site, || Ok(f())) {
+			Ok(value) => value,
+			Err(never) => match never {},
+		}
+	}
+
+	/// Like [`get_or_init`](Self::get_or_init), but if `f` fails the cell
+	/// stays empty and the error is returned. The next caller runs `f` again.
+	#[inline]
+	pub fn get_or_try_init<W: WaitStrategy, E>(
+		&self,
+		site: &'static Site,
+		f: impl FnOnce() -> Result<T, E>,
+	) -> Result<&T, E> {
+		if self.state.load(Ordering::Acquire) == COMPLETE {
+			// SAFETY: `COMPLETE` is only stored after the value is written.
+			return Ok(unsafe { self.get_unchecked() });
+		}
+
+		self.initialize::<W, E>(site, f)
+	}
+
+	/// Returns the value if the cell is initialized, without initializing it.
+	#[inline]
+	pub fn get(&self) -> Option<&T> {
+		// SAFETY: `COMPLETE` is only stored after the value is written.
+		self.is_initialized()
+			.then(|| unsafe { self.get_unchecked() })
+	}
+
+	/// Whether the value is there.
+	#[inline]
+	pub fn is_initialized(&self) -> bool {
+		self.state.load(Ordering::Acquire) == COMPLETE
+	}
+
+	/// Whether a previous initializer panicked.
+	#[inline]
+	pub fn is_poisoned(&self) -> bool {
+		self.state.load(Ordering::Acquire) == POISONED
+	}
+
+	/// Lets the next access run the initializer again after it panicked.
+	/// Does nothing if the cell isn't poisoned.
+	#[inline]
+	#[cfg(not(feature = "critical-section"))]
+	pub fn clear_poison(&self) {
+		_ = self
+			.state
+			.compare_exchange(POISONED, UNINIT, Ordering::Relaxed, Ordering::Relaxed);
+	}
+
+	/// Lets the next access run the initializer again after it panicked.
+	/// Does nothing if the cell isn't poisoned.
+	#[cfg(feature = "critical-section")]
+	pub fn clear_poison(&self) {
+		critical_section::with(|_| {
+			if self.state.load(Ordering::Relaxed) == POISONED {
+				self.state.store(UNINIT, Ordering::Relaxed);
+			}
+		});
+	}
+
+	/// Drops the value and clears the poison, so the next access runs the
+	/// initializer again.
+	///
+	/// # Safety
+	///
+	/// No references to the value can be alive.
+	///
+	/// # Panics
+	///
+	/// If the initializer is running.
+	#[cfg(feature = "testing")]
+	pub unsafe fn reset(&self, site: &'static Site) {
+		match self
+			.state
+			.compare_exchange(COMPLETE, RUNNING, Ordering::Acquire, Ordering::Acquire)
+		{
+			Ok(_) => {
+				let guard = Poison(self);
+				(*self.value.get()).assume_init_drop();
+				core::mem::forget(guard);
+				self.publish(UNINIT);
+			}
+			Err(RUNNING) => panic!("slazy: can't reset {site} while it's being initialized"),
+			Err(_) => self.clear_poison(),
+		}
+	}
+
+	/// Stores `value` if nobody has started initializing the cell yet, so the
+	/// initializer never runs. Otherwise gives `value` back.
+	#[cfg(not(feature = "critical-section"))]
+	pub fn set(&self, value: T) -> Result<(), T> {
+		if self
+			.state
+			.compare_exchange(UNINIT, RUNNING, Ordering::Acquire, Ordering::Relaxed)
+			.is_err()
+		{
+			return Err(value);
+		}
+
+		// SAFETY: the CAS just gave us `RUNNING`.
+		_ = unsafe { self.run(|| Ok::<T, Infallible>(value)) };
+		Ok(())
+	}
+
+	/// Stores `value` if nobody has started initializing the cell yet, so the
+	/// initializer never runs. Otherwise gives `value` back.
+	#[cfg(feature = "critical-section")]
+	pub fn set(&self, value: T) -> Result<(), T> {
+		critical_section::with(|_| {
+			if self.state.load(Ordering::Acquire) != UNINIT {
+				return Err(value);
+			}
+
+			self.state.store(RUNNING, Ordering::Relaxed);
+			// SAFETY: we're in a critical section and just set `RUNNING`.
+			_ = unsafe { self.run(|| Ok::<T, Infallible>(value)) };
+			Ok(())
+		})
+	}
+
+	#[cold]
+	#[cfg(not(feature = "critical-section"))]
+	fn initialize<W: WaitStrategy, E>(
+		&self,
+		site: &'static Site,
+		f: impl FnOnce() -> Result<T, E>,
+	) -> Result<&T, E> {
+		loop {
+			match self.state.compare_exchange_weak(
+				UNINIT,
+				RUNNING,
+				Ordering::Acquire,
+				Ordering::Acquire,
+			) {
+				Ok(_) => {
+					#[cfg(feature = "std")]
+					self.owner
+						.store(crate::thread::current(), Ordering::Relaxed);
+					// SAFETY: the CAS just gave us `RUNNING`.
+					return unsafe { self.run(f) };
+				}
+				// SAFETY: `COMPLETE` is only stored after the value is written.
+				Err(COMPLETE) => return Ok(unsafe { self.get_unchecked() }),
+				Err(RUNNING) => {
+					// Waiting for ourselves would never end.
+					#[cfg(feature = "std")]
+					if self.owner.load(Ordering::Relaxed) == crate::thread::current() {
+						recursive(site);
+					}
+
+					#[cfg(feature = "debug-deadlock")]
+					let _waiting = crate::deadlock::Waiting::start(site, &self.owner);
+
+					W::wait_until(|| self.state.load(Ordering::Acquire) != RUNNING)
+				}
+				Err(POISONED) => poisoned(site),
+				// Spurious failure of the weak CAS.
+				Err(_) => {}
+			}
+		}
+	}
+
+	/// Runs the whole initializer inside a critical section. Nothing can
+	/// interrupt it, so nobody ever has to wait for it, and only plain atomic
+	/// loads and stores are needed.
+	#[cold]
+	#[cfg(feature = "critical-section")]
+	#[allow(clippy::extra_unused_type_parameters)]
+	fn initialize<W: WaitStrategy, E>(
+		&self,
+		site: &'static Site,
+		f: impl FnOnce() -> Result<T, E>,
+	) -> Result<&T, E> {
+		critical_section::with(|_| match self.state.load(Ordering::Acquire) {
+			UNINIT => {
+				self.state.store(RUNNING, Ordering::Relaxed);
+				// SAFETY: we're in a critical section and just set `RUNNING`.
+				unsafe { self.run(f) }
+			}
+			// SAFETY: `COMPLETE` is only stored after the value is written.
+			COMPLETE => Ok(unsafe { self.get_unchecked() }),
+			POISONED => poisoned(site),
+			// Whoever set `RUNNING` is holding the critical section, and that's us.
+			_ => recursive(site),
+		})
+	}
+
+	/// Runs `f`. If it succeeds, stores its result and marks the cell as
+	/// `COMPLETE`. If it fails, moves it back to `UNINIT`, and if it panics,
+	/// to `POISONED`.
+	///
+	/// # Safety
+	///
+	/// The caller must be the one that moved the cell to `RUNNING`.
+	unsafe fn run<E>(&self, f: impl FnOnce() -> Result<T, E>) -> Result<&T, E> {
+		let guard = Poison(self);
+		let result = f();
+		core::mem::forget(guard);
+
+		match result {
+			Ok(value) => {
+				(*self.value.get()).write(value);
+				self.publish(COMPLETE);
+				Ok(self.get_unchecked())
+			}
+			Err(err) => {
+				self.publish(UNINIT);
+				Err(err)
+			}
+		}
+	}
+
+	/// Leaves `RUNNING` and wakes up whoever was waiting for it.
+	#[inline]
+	fn publish(&self, state: u8) {
+		#[cfg(all(feature = "std", not(feature = "critical-section")))]
+		self.owner.store(0, Ordering::Relaxed);
+		self.state.store(state, Ordering::Release);
+		wait::notify_all();
+	}
+
+	/// # Safety
+	///
+	/// The cell must be `COMPLETE`.
+	#[inline(always)]
+	unsafe fn get_unchecked(&self) -> &T {
+		(*self.value.get()).assume_init_ref()
+	}
+}
+
+impl<T> Default for LazyCell<T> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<T> Drop for LazyCell<T> {
+	fn drop(&mut self) {
+		if *self.state.get_mut() == COMPLETE {
+			// SAFETY: the value is initialized and we have unique access.
+			unsafe { self.value.get_mut().assume_init_drop() }
+		}
+	}
+}
+
+#[cold]
+#[inline(never)]
+pub(crate) fn poisoned(site: &'static Site) -> ! {
+	panic!("slazy: {site} is poisoned: its initializer panicked")
+}
+
+#[cold]
+#[inline(never)]
+#[cfg(any(feature = "std", feature = "critical-section"))]
+fn recursive(site: &'static Site) -> ! {
+	panic!("slazy: recursive initialization of {site}")
+}
+
+/// Poisons the cell if the initializer unwinds.
+struct Poison<'a, T>(&'a LazyCell<T>);
+
+impl<T> Drop for Poison<'_, T> {
+	fn drop(&mut self) {
+		self.0.publish(POISONED);
+	}
+}
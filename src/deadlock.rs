@@ -0,0 +1,109 @@
+//! Finds initializers that wait on each other across threads.
+//!
+//! Every thread that's about to wait for another thread's initializer writes
+//! down which static it's waiting for. Following "thread waits for static,
+//! static is being initialized by thread" from there tells us whether the
+//! wait would close a cycle, in which case nobody would ever wake up.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::string::String;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::vec::Vec;
+
+use crate::{thread, Site};
+
+struct Wait {
+	thread: usize,
+	site: &'static Site,
+	/// The `owner` of the cell being waited for.
+	owner: *const AtomicUsize,
+}
+
+// SAFETY: `owner` is only dereferenced while its `Wait` is in `WAITS`, and
+// the `Waiting` that put it there borrows the cell until it takes it out.
+unsafe impl Send for Wait {}
+
+static WAITS: Mutex<Vec<Wait>> = Mutex::new(Vec::new());
+
+fn waits() -> MutexGuard<'static, Vec<Wait>> {
+	WAITS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Marks the current thread as waiting for a static for as long as it lives.
+pub(crate) struct Waiting<'a> {
+	thread: usize,
+	_owner: &'a AtomicUsize,
+}
+
+impl<'a> Waiting<'a> {
+	/// Starts waiting for the static at `site`, whose initializer is being run
+	/// by the thread in `owner`.
+	///
+	/// # Panics
+	///
+	/// If that thread is (maybe through others) waiting for the current one.
+	pub(crate) fn start(site: &'static Site, owner: &'a AtomicUsize) -> Self {
+		let thread = thread::current();
+		let mut waits = waits();
+		let mut cycle = Vec::from([site]);
+		let mut holder = owner.load(Ordering::Relaxed);
+
+		// Every step visits a different waiting thread, unless there's a cycle
+		// we aren't part of, which can't happen: whoever closed it would have
+		// panicked here instead of waiting.
+		for _ in 0..=waits.len() {
+			if holder == thread {
+				drop(waits);
+				cycle.push(site);
+				deadlock(&cycle);
+			}
+
+			let Some(wait) = waits.iter().find(|wait| wait.thread == holder) else {
+				break;
+			};
+
+			cycle.push(wait.site);
+			// SAFETY: see `impl Send for Wait`.
+			holder = unsafe { &*wait.owner }.load(Ordering::Relaxed);
+		}
+
+		waits.push(Wait {
+			thread,
+			site,
+			owner,
+		});
+
+		Self {
+			thread,
+			_owner: owner,
+		}
+	}
+}
+
+impl Drop for Waiting<'_> {
+	fn drop(&mut self) {
+		waits().retain(|wait| wait.thread != self.thread);
+	}
+}
+
+#[cold]
+#[inline(never)]
+fn deadlock(cycle: &[&'static Site]) -> ! {
+	let mut path = String::new();
+
+	for (i, site) in cycle.iter().enumerate() {
+		if i > 0 {
+			path.push_str(" -> ");
+		}
+
+		path.push('`');
+		path.push_str(site.name);
+		path.push('`');
+	}
+
+	panic!(
+		"slazy: deadlock: {path}, the initializers of these statics are waiting for each other \
+		 (first declared at {}:{})",
+		cycle[0].file, cycle[0].line
+	)
+}
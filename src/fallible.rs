@@ -0,0 +1,180 @@
+//! Support for lazy statics whose initializer can fail.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::wait::WaitStrategy;
+use crate::{LazyCell, Site};
+
+/// Implemented by the `Result` type of a fallible lazy static
+/// (`NAME: Result<T, E> = try ...;`), to find out `T` and `E` from it.
+#[diagnostic::on_unimplemented(
+	message = "`try` initializers need a `Result<T, E>` type, found `{Self}`",
+	label = "expected `Result<T, E>`"
+)]
+pub trait Fallible {
+	/// The type of the value on success.
+	type Ok;
+	/// The type of the error on failure.
+	type Err;
+
+	/// Borrows the `Result`.
+	fn as_result(&self) -> Result<&Self::Ok, &Self::Err>;
+}
+
+impl<T, E> Fallible for Result<T, E> {
+	type Ok = T;
+	type Err = E;
+
+	#[inline(always)]
+	fn as_result(&self) -> Result<&T, &E> {
+		self.as_ref()
+	}
+}
+
+/// What a fallible lazy static does when its initializer fails.
+///
+/// Picked with an attribute on the static:
+///
+/// - `#[retry]`: [`RetryPolicy::Always`].
+/// - `#[retry(n)]`: [`RetryPolicy::UpTo(n)`](RetryPolicy::UpTo).
+///
+/// Without one, the first error is kept forever.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryPolicy {
+	/// Never keep the error, run the initializer again on the next access.
+	Always,
+	/// Run the initializer at most this many times, then keep the last error.
+	UpTo(usize),
+}
+
+impl RetryPolicy {
+	/// Whether the error from the `failures`th failed attempt should be kept.
+	#[inline]
+	fn gives_up_after(self, failures: usize) -> bool {
+		match self {
+			Self::Always => false,
+			Self::UpTo(max) => failures >= max,
+		}
+	}
+}
+
+/// The error of a fallible lazy static that retries its initializer.
+#[derive(Debug)]
+pub enum InitError<E: 'static> {
+	/// The initializer failed on this access, and will run again on the next one.
+	Failed(E),
+	/// The initializer failed for good, and this is the error it gave.
+	Cached(&'static E),
+}
+
+impl<E> InitError<E> {
+	/// Borrows the error, wherever it lives.
+	#[inline]
+	pub fn error(&self) -> &E {
+		match self {
+			Self::Failed(err) => err,
+			Self::Cached(err) => err,
+		}
+	}
+}
+
+impl<E: core::fmt::Display> core::fmt::Display for InitError<E> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		self.error().fmt(f)
+	}
+}
+
+/// The storage behind a fallible lazy static with a [`RetryPolicy`].
+pub struct RetryCell<T, E> {
+	cell: LazyCell<Result<T, E>>,
+	failures: AtomicUsize,
+}
+
+impl<T, E> RetryCell<T, E> {
+	/// Creates an empty cell.
+	#[inline]
+	pub const fn new() -> Self {
+		Self {
+			cell: LazyCell::new(),
+			failures: AtomicUsize::new(0),
+		}
+	}
+
+	/// Returns the value, running `f` first if needed. `policy` decides whether
+	/// a failure is kept or `f` runs again next time.
+	#[inline]
+	pub fn get_or_init<W: WaitStrategy>(
+		&'static self,
+		site: &'static Site,
+		policy: RetryPolicy,
+		f: impl FnOnce() -> Result<T, E>,
+	) -> Result<&'static T, InitError<E>> {
+		let result = self.cell.get_or_try_init::<W, E>(site, || match f() {
+			Ok(value) => Ok(Ok(value)),
+			Err(err) => {
+				// Only the thread running the initializer gets here, so
+				// there's nothing to synchronize with. A plain load and store
+				// also works on targets without read-modify-write atomics.
+				let failures = self.failures.load(Ordering::Relaxed) + 1;
+				self.failures.store(failures, Ordering::Relaxed);
+
+				if policy.gives_up_after(failures) {
+					Ok(Err(err))
+				} else {
+					Err(err)
+				}
+			}
+		});
+
+		match result {
+			Ok(Ok(value)) => Ok(value),
+			Ok(Err(err)) => Err(InitError::Cached(err)),
+			Err(err) => Err(InitError::Failed(err)),
+		}
+	}
+
+	/// Returns the value or the cached error, without running the
+	/// initializer.
+	#[inline]
+	pub fn get(&'static self) -> Option<Result<&'static T, InitError<E>>> {
+		self.cell
+			.get()
+			.map(|result| result.as_ref().map_err(InitError::Cached))
+	}
+
+	/// Whether the initializer finished, with a value or an error that is
+	/// kept.
+	#[inline]
+	pub fn is_initialized(&self) -> bool {
+		self.cell.is_initialized()
+	}
+
+	/// Whether a previous initializer panicked.
+	#[inline]
+	pub fn is_poisoned(&self) -> bool {
+		self.cell.is_poisoned()
+	}
+
+	/// Lets the next access run the initializer again after it panicked.
+	#[inline]
+	pub fn clear_poison(&self) {
+		self.cell.clear_poison();
+	}
+
+	/// Drops the value or cached error and forgets about previous failures.
+	///
+	/// # Safety
+	///
+	/// See [`LazyCell::reset`].
+	#[cfg(feature = "testing")]
+	pub unsafe fn reset(&self, site: &'static Site) {
+		self.cell.reset(site);
+		self.failures.store(0, Ordering::Relaxed);
+	}
+}
+
+impl<T, E> Default for RetryCell<T, E> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
@@ -1,6 +1,42 @@
 #![no_std]
 #![doc = include_str!("../README.md")]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+mod cell;
+#[cfg(all(feature = "debug-deadlock", not(feature = "critical-section")))]
+mod deadlock;
+mod fallible;
+mod mutex;
+pub mod per_task;
+#[cfg(feature = "alloc")]
+pub mod reload;
+mod rwlock;
+#[cfg(all(feature = "sighup", unix))]
+pub mod sighup;
+mod site;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(all(feature = "std", not(feature = "critical-section")))]
+mod thread;
+pub mod wait;
+
+#[doc(hidden)]
+pub use cell::LazyCell;
+#[doc(hidden)]
+pub use fallible::{Fallible, RetryCell};
+pub use fallible::{InitError, RetryPolicy};
+pub use mutex::{Mutex, MutexGuard};
+pub use rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+#[doc(hidden)]
+pub use site::Site;
+#[cfg(feature = "std")]
+#[doc(hidden)]
+pub use std::thread_local as __thread_local;
+
 /// The macro to create a lazy static.
 ///
 /// # Usage
@@ -10,50 +46,1099 @@
 ///
 /// slazy! {
 ///     pub public_var: String = String::from("Hello, world!");
+///     pub(crate) crate_var: u32 = 7;
 ///     non_public_example: u32 = 42;
 /// }
 /// ```
+///
+/// Any visibility works (`pub`, `pub(crate)`, `pub(super)`, `pub(in path)` or
+/// none), and applies to the generated struct and its methods. The `;` after
+/// the last declaration is optional, and the macro can also be called as
+/// `slazy::slazy!` without importing it.
+///
+/// Doc comments and other attributes go on the generated struct. `#[cfg(...)]`
+/// and `#[allow(...)]` also apply to everything generated for it, so a lazy
+/// static that is configured out disappears completely:
+///
+/// ```
+/// use slazy::slazy;
+///
+/// slazy! {
+///     /// How many workers to spawn.
+///     pub WORKERS: usize = 4;
+///
+///     #[cfg(any())]
+///     pub NEVER_COMPILED: u32 = this_function_does_not_exist();
+///
+///     #[deprecated(note = "use `WORKERS`")]
+///     pub THREADS: usize = *WORKERS;
+/// }
+///
+/// assert_eq!(*WORKERS, 4);
+/// ```
+///
+/// The initializer runs exactly once, no matter how many threads race to
+/// access the value first. Because of that, the type must be `Send + Sync`.
+///
+/// Threads that lose that race wait using [`wait::DefaultWait`], unless the
+/// static picks another [`WaitStrategy`](wait::WaitStrategy) with `#[wait(...)]`:
+///
+/// ```
+/// use slazy::{slazy, wait::Yield};
+///
+/// slazy! {
+///     #[wait(Yield)]
+///     pub BIG_TABLE: Vec<u64> = (0..1024).collect();
+/// }
+/// ```
+///
+/// # Fallible initializers
+///
+/// If the initializer returns a `Result`, put `try` in front of it. The
+/// static's type is the whole `Result<T, E>`, and it gets a `get()` method that
+/// returns `Result<&'static T, &'static E>`. Whatever comes out of the
+/// initializer, value or error, is cached.
+///
+/// Dereferencing it gives you `T` directly, and panics with the error if the
+/// initializer failed (so `E` has to implement `Debug`).
+///
+/// ```
+/// use slazy::slazy;
+///
+/// slazy! {
+///     PORT: Result<u16, core::num::ParseIntError> = try "8080".parse();
+///     BROKEN: Result<u16, core::num::ParseIntError> = try "eighty".parse();
+/// }
+///
+/// assert_eq!(*PORT, 8080);
+/// assert!(BROKEN.get().is_err());
+/// ```
+///
+/// To run the initializer again after it fails instead of caching the error,
+/// add `#[retry]` (retry on every access) or `#[retry(n)]` (run it at most `n`
+/// times). See [`RetryPolicy`]. `get()` then returns an [`InitError`], which
+/// owns the error unless it's the one that ended up cached:
+///
+/// ```
+/// use slazy::{slazy, InitError};
+/// use std::sync::atomic::{AtomicU32, Ordering};
+///
+/// static ATTEMPTS: AtomicU32 = AtomicU32::new(0);
+///
+/// slazy! {
+///     #[retry(3)]
+///     FLAKY: Result<u32, u32> = try Err(ATTEMPTS.fetch_add(1, Ordering::SeqCst));
+/// }
+///
+/// assert!(matches!(FLAKY.get(), Err(InitError::Failed(0))));
+/// assert!(matches!(FLAKY.get(), Err(InitError::Failed(1))));
+/// assert!(matches!(FLAKY.get(), Err(InitError::Cached(2))));
+/// assert!(matches!(FLAKY.get(), Err(InitError::Cached(2))));
+/// ```
+///
+/// # Poisoning
+///
+/// If an initializer panics, the static is poisoned: every later access panics
+/// too, instead of quietly running the initializer again. Use `is_poisoned()`
+/// to check for it, and `clear_poison()` to give the initializer another go.
+///
+/// ```
+/// use slazy::slazy;
+/// use std::panic;
+/// use std::sync::atomic::{AtomicBool, Ordering};
+///
+/// static READY: AtomicBool = AtomicBool::new(false);
+///
+/// slazy! {
+///     DB: &'static str = {
+///         assert!(READY.load(Ordering::SeqCst), "not ready yet");
+///         "connected"
+///     };
+/// }
+///
+/// assert!(panic::catch_unwind(|| *DB).is_err());
+/// assert!(DB.is_poisoned());
+/// assert!(panic::catch_unwind(|| *DB).is_err()); // still poisoned
+///
+/// READY.store(true, Ordering::SeqCst);
+/// DB.clear_poison();
+/// assert_eq!(*DB, "connected");
+/// ```
+///
+/// # Looking without initializing
+///
+/// `is_initialized()` tells whether the value is there, and `try_get()`
+/// returns it if it is (`None` otherwise). Neither runs the initializer.
+///
+/// ```
+/// use slazy::slazy;
+///
+/// slazy! {
+///     pub EXPENSIVE: Vec<u64> = (0..1_000).collect();
+/// }
+///
+/// assert!(!EXPENSIVE.is_initialized());
+/// assert_eq!(EXPENSIVE.try_get(), None);
+///
+/// assert_eq!(EXPENSIVE.len(), 1_000);
+/// assert!(EXPENSIVE.is_initialized());
+/// assert_eq!(EXPENSIVE.try_get().map(Vec::len), Some(1_000));
+/// ```
+///
+/// `try_get()` returns what the static gives access to: `get()`'s result for
+/// `try` statics, the lock for `mut` and `rw` ones, and a snapshot for `reload`
+/// ones.
+///
+/// With the `std` feature, `wait()` blocks until someone else initializes the
+/// static, and `wait_timeout(duration)` gives up after a while. `#[per_task]`
+/// statics don't have them, since only the task itself can initialize its
+/// value:
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// use slazy::slazy;
+/// use std::time::Duration;
+///
+/// slazy! {
+///     pub MODEL: Vec<f32> = vec![0.5; 1024];
+/// }
+///
+/// let worker = std::thread::spawn(|| {
+///     MODEL.wait();
+///     MODEL.len()
+/// });
+///
+/// assert!(!MODEL.wait_timeout(Duration::from_millis(10)));
+/// assert_eq!(MODEL.len(), 1024);
+/// assert_eq!(worker.join().unwrap(), 1024);
+/// # }
+/// ```
+///
+/// # Setting the value up front
+///
+/// `set(value)` installs a value before the first access, so the initializer
+/// never runs. This is handy for injecting fakes in tests. If the static was
+/// already accessed, the value is given back instead.
+///
+/// ```
+/// use slazy::slazy;
+///
+/// fn connect() -> String {
+///     unreachable!("no database in tests")
+/// }
+///
+/// slazy! {
+///     pub DB: String = connect();
+/// }
+///
+/// assert_eq!(DB.set(String::from("fake")), Ok(()));
+/// assert_eq!(*DB, "fake");
+/// assert_eq!(DB.set(String::from("too late")), Err(String::from("too late")));
+/// ```
+///
+/// `mut` and `rw` statics have it too.
+///
+/// # Recursive initialization
+///
+/// An initializer that (maybe through other lazy statics) needs its own value
+/// panics with a message like ``slazy: recursive initialization of `FOO`
+/// (declared at src/config.rs:12)``. This needs either the `std` or the
+/// `critical-section` feature; without them there's no way to tell recursion
+/// apart from another thread running the initializer, so it never finishes.
+///
+/// ```no_run
+/// use slazy::slazy;
+///
+/// slazy! {
+///     FOO: u32 = *BAR + 1;
+///     BAR: u32 = *FOO + 1; // panics: recursive initialization of `FOO`
+/// }
+///
+/// println!("{}", *FOO);
+/// ```
+///
+/// # Mutable lazy statics
+///
+/// Put `mut` in front of the name to wrap the value in a [`Mutex`]. Instead
+/// of `Deref`, the static gets `lock()` and `with_mut(|value| ...)`, so the
+/// type only needs to be `Send`.
+///
+/// ```
+/// use slazy::slazy;
+///
+/// slazy! {
+///     pub mut CACHE: Vec<u8> = Vec::new();
+/// }
+///
+/// CACHE.lock().push(1);
+/// CACHE.with_mut(|cache| cache.push(2));
+/// assert_eq!(*CACHE.lock(), [1, 2]);
+/// ```
+///
+/// For values that are read much more often than they're written, use `rw`
+/// instead to get a [`RwLock`], with `read()` and `write()`:
+///
+/// ```
+/// use slazy::slazy;
+///
+/// slazy! {
+///     pub rw FLAGS: Vec<&'static str> = vec!["fast-path"];
+/// }
+///
+/// assert!(FLAGS.read().contains(&"fast-path"));
+/// FLAGS.write().push("new-ui");
+/// assert_eq!(FLAGS.read().len(), 2);
+/// ```
+///
+/// # Reloadable lazy statics
+///
+/// With the `alloc` feature, `reload` in front of the name gives a static
+/// whose value can be replaced at runtime with `store()` or `reload()`, and
+/// read with `load()`. See the `reload` module.
+///
+/// `derive(INPUT => |input| ...)` as the initializer gives a static computed
+/// from a reloadable one, which is computed again after the input changes.
+///
+/// # Per-task values
+///
+/// `#[per_task(Provider, N)]` gives every task its own value, without needing
+/// `std`. See the [`per_task`] module.
 #[macro_export]
 macro_rules! slazy {
-    (pub $name:ident: $type:ty = $val:expr; $($rest:tt)*) => {
-        pub struct $name;
-        $crate::__internal_inner_slazy!($name, $type, $val);
-        slazy!($($rest)*);
-    };
-    ($name:ident: $type:ty = $val:expr; $($rest:tt)*) => {
-        struct $name;
-        $crate::__internal_inner_slazy!($name, $type, $val);
-        slazy!($($rest)*);
-    };
-    (pub $name:ident: $type:ty = $val:expr) => {
-        pub struct $name;
-        $crate::__internal_inner_slazy!($name, $type, $val);
-    };
-    ($name:ident: $type:ty = $val:expr) => {
-        struct $name;
-        $crate::__internal_inner_slazy!($name, $type, $val);
-    };
 	() => {};
+
+	// Options (`#[wait(...)]`, `#[retry(...)]`, `#[per_task(...)]`) and other
+	// attributes can come in any order, so they are collected first:
+	// `[wait] [retry policy] [task, slots] [cfgs and lints] [other attributes]`.
+	(@entry [$($wait:ty)?] $retry:tt $task:tt $cfg:tt $attr:tt #[wait($new_wait:ty)] $($rest:tt)*) => {
+		$crate::slazy!(@entry [$new_wait] $retry $task $cfg $attr $($rest)*);
+	};
+	(@entry $wait:tt [$($retry:expr)?] $task:tt $cfg:tt $attr:tt #[retry] $($rest:tt)*) => {
+		$crate::slazy!(@entry $wait [$crate::RetryPolicy::Always] $task $cfg $attr $($rest)*);
+	};
+	(@entry $wait:tt [$($retry:expr)?] $task:tt $cfg:tt $attr:tt #[retry($max:expr)] $($rest:tt)*) => {
+		$crate::slazy!(@entry $wait [$crate::RetryPolicy::UpTo($max)] $task $cfg $attr $($rest)*);
+	};
+	(@entry $wait:tt $retry:tt [$($task:ty, $slots:expr)?] $cfg:tt $attr:tt #[per_task($new_task:ty, $new_slots:expr)] $($rest:tt)*) => {
+		$crate::slazy!(@entry $wait $retry [$new_task, $new_slots] $cfg $attr $($rest)*);
+	};
+	// `cfg` and `allow` also go on the unnamed const holding the generated
+	// impls, so a disabled lazy disappears completely and lints in the
+	// initializer can be silenced. The rest only go on the struct.
+	(@entry $wait:tt $retry:tt $task:tt [$($cfg:tt)*] $attr:tt #[cfg($($predicate:tt)*)] $($rest:tt)*) => {
+		$crate::slazy!(@entry $wait $retry $task [$($cfg)* #[cfg($($predicate)*)]] $attr $($rest)*);
+	};
+	(@entry $wait:tt $retry:tt $task:tt [$($cfg:tt)*] $attr:tt #[allow($($lints:tt)*)] $($rest:tt)*) => {
+		$crate::slazy!(@entry $wait $retry $task [$($cfg)* #[allow($($lints)*)]] $attr $($rest)*);
+	};
+	// The generated impls use the deprecated struct, which would warn. Only
+	// deprecated entries get the `allow`, so deprecated functions called
+	// from other initializers still do.
+	(@entry $wait:tt $retry:tt $task:tt [$($cfg:tt)*] [$($attr:tt)*] #[deprecated $($args:tt)*] $($rest:tt)*) => {
+		$crate::slazy!(@entry $wait $retry $task [$($cfg)* #[allow(deprecated)]] [$($attr)* #[deprecated $($args)*]] $($rest)*);
+	};
+	(@entry $wait:tt $retry:tt $task:tt $cfg:tt [$($attr:tt)*] #[$meta:meta] $($rest:tt)*) => {
+		$crate::slazy!(@entry $wait $retry $task $cfg [$($attr)* #[$meta]] $($rest)*);
+	};
+
+	(@entry [$($wait:ty)?] [] [] [$(#[$cfg:meta])*] [$(#[$attr:meta])*] $vis:vis $name:ident: $type:ty = try $val:expr; $($rest:tt)*) => {
+		$(#[$cfg])*
+		$(#[$attr])*
+		$vis struct $name;
+		$(#[$cfg])*
+		const _: () = {
+			$crate::__internal_inner_slazy!(try $vis $name, $type, $val $(, $wait)?);
+		};
+		$crate::slazy!($($rest)*);
+	};
+	(@entry [$($wait:ty)?] [] [] [$(#[$cfg:meta])*] [$(#[$attr:meta])*] $vis:vis $name:ident: $type:ty = try $val:expr) => {
+		$(#[$cfg])*
+		$(#[$attr])*
+		$vis struct $name;
+		$(#[$cfg])*
+		const _: () = {
+			$crate::__internal_inner_slazy!(try $vis $name, $type, $val $(, $wait)?);
+		};
+	};
+
+	(@entry [$($wait:ty)?] [$retry:expr] [] [$(#[$cfg:meta])*] [$(#[$attr:meta])*] $vis:vis $name:ident: $type:ty = try $val:expr; $($rest:tt)*) => {
+		$(#[$cfg])*
+		$(#[$attr])*
+		$vis struct $name;
+		$(#[$cfg])*
+		const _: () = {
+			$crate::__internal_inner_slazy!(retry $vis $name, $type, $val, $retry $(, $wait)?);
+		};
+		$crate::slazy!($($rest)*);
+	};
+	(@entry [$($wait:ty)?] [$retry:expr] [] [$(#[$cfg:meta])*] [$(#[$attr:meta])*] $vis:vis $name:ident: $type:ty = try $val:expr) => {
+		$(#[$cfg])*
+		$(#[$attr])*
+		$vis struct $name;
+		$(#[$cfg])*
+		const _: () = {
+			$crate::__internal_inner_slazy!(retry $vis $name, $type, $val, $retry $(, $wait)?);
+		};
+	};
+	(@entry [$($wait:ty)?] [$retry:expr] [] [$(#[$cfg:meta])*] [$(#[$attr:meta])*] $($rest:tt)*) => {
+		::core::compile_error!("`#[retry]` can only be used on `try` initializers");
+	};
+
+	(@entry [$($wait:ty)?] [] [$task:ty, $slots:expr] [$(#[$cfg:meta])*] [$(#[$attr:meta])*] $vis:vis $name:ident: $type:ty = try $($rest:tt)*) => {
+		::core::compile_error!("`#[per_task]` can't be used on `try` initializers");
+	};
+	(@entry [$($wait:ty)?] [] [$task:ty, $slots:expr] [$(#[$cfg:meta])*] [$(#[$attr:meta])*] $vis:vis $name:ident: $type:ty = $val:expr; $($rest:tt)*) => {
+		$(#[$cfg])*
+		$(#[$attr])*
+		$vis struct $name;
+		$(#[$cfg])*
+		const _: () = {
+			$crate::__internal_inner_slazy!(per_task $vis $name, $type, $val, $task, $slots $(, $wait)?);
+		};
+		$crate::slazy!($($rest)*);
+	};
+	(@entry [$($wait:ty)?] [] [$task:ty, $slots:expr] [$(#[$cfg:meta])*] [$(#[$attr:meta])*] $vis:vis $name:ident: $type:ty = $val:expr) => {
+		$(#[$cfg])*
+		$(#[$attr])*
+		$vis struct $name;
+		$(#[$cfg])*
+		const _: () = {
+			$crate::__internal_inner_slazy!(per_task $vis $name, $type, $val, $task, $slots $(, $wait)?);
+		};
+	};
+	(@entry [$($wait:ty)?] [$retry:expr] [$task:ty, $slots:expr] [$(#[$cfg:meta])*] [$(#[$attr:meta])*] $($rest:tt)*) => {
+		::core::compile_error!("`#[retry]` can only be used on `try` initializers");
+	};
+
+	(@entry [$($wait:ty)?] [] [] [$(#[$cfg:meta])*] [$(#[$attr:meta])*] $vis:vis mut $name:ident: $type:ty = $val:expr; $($rest:tt)*) => {
+		$(#[$cfg])*
+		$(#[$attr])*
+		$vis struct $name;
+		$(#[$cfg])*
+		const _: () = {
+			$crate::__internal_inner_slazy!(mut $vis $name, $type, $val $(, $wait)?);
+		};
+		$crate::slazy!($($rest)*);
+	};
+	(@entry [$($wait:ty)?] [] [] [$(#[$cfg:meta])*] [$(#[$attr:meta])*] $vis:vis mut $name:ident: $type:ty = $val:expr) => {
+		$(#[$cfg])*
+		$(#[$attr])*
+		$vis struct $name;
+		$(#[$cfg])*
+		const _: () = {
+			$crate::__internal_inner_slazy!(mut $vis $name, $type, $val $(, $wait)?);
+		};
+	};
+
+	(@entry [$($wait:ty)?] [] [] [$(#[$cfg:meta])*] [$(#[$attr:meta])*] $vis:vis reload $name:ident: $type:ty = $val:expr; $($rest:tt)*) => {
+		$(#[$cfg])*
+		$(#[$attr])*
+		$vis struct $name;
+		$(#[$cfg])*
+		const _: () = {
+			$crate::__internal_inner_slazy!(reload $vis $name, $type, $val $(, $wait)?);
+		};
+		$crate::slazy!($($rest)*);
+	};
+	(@entry [$($wait:ty)?] [] [] [$(#[$cfg:meta])*] [$(#[$attr:meta])*] $vis:vis reload $name:ident: $type:ty = $val:expr) => {
+		$(#[$cfg])*
+		$(#[$attr])*
+		$vis struct $name;
+		$(#[$cfg])*
+		const _: () = {
+			$crate::__internal_inner_slazy!(reload $vis $name, $type, $val $(, $wait)?);
+		};
+	};
+
+	(@entry [$($wait:ty)?] [] [] [$(#[$cfg:meta])*] [$(#[$attr:meta])*] $vis:vis rw $name:ident: $type:ty = $val:expr; $($rest:tt)*) => {
+		$(#[$cfg])*
+		$(#[$attr])*
+		$vis struct $name;
+		$(#[$cfg])*
+		const _: () = {
+			$crate::__internal_inner_slazy!(rw $vis $name, $type, $val $(, $wait)?);
+		};
+		$crate::slazy!($($rest)*);
+	};
+	(@entry [$($wait:ty)?] [] [] [$(#[$cfg:meta])*] [$(#[$attr:meta])*] $vis:vis rw $name:ident: $type:ty = $val:expr) => {
+		$(#[$cfg])*
+		$(#[$attr])*
+		$vis struct $name;
+		$(#[$cfg])*
+		const _: () = {
+			$crate::__internal_inner_slazy!(rw $vis $name, $type, $val $(, $wait)?);
+		};
+	};
+
+	(@entry [$($wait:ty)?] [] [] [$(#[$cfg:meta])*] [$(#[$attr:meta])*] $vis:vis $name:ident: $type:ty = derive($input:ident => $f:expr); $($rest:tt)*) => {
+		$(#[$cfg])*
+		$(#[$attr])*
+		$vis struct $name;
+		$(#[$cfg])*
+		const _: () = {
+			$crate::__internal_inner_slazy!(derive $vis $name, $type, $input, $f $(, $wait)?);
+		};
+		$crate::slazy!($($rest)*);
+	};
+	(@entry [$($wait:ty)?] [] [] [$(#[$cfg:meta])*] [$(#[$attr:meta])*] $vis:vis $name:ident: $type:ty = derive($input:ident => $f:expr)) => {
+		$(#[$cfg])*
+		$(#[$attr])*
+		$vis struct $name;
+		$(#[$cfg])*
+		const _: () = {
+			$crate::__internal_inner_slazy!(derive $vis $name, $type, $input, $f $(, $wait)?);
+		};
+	};
+
+	(@entry [$($wait:ty)?] [] [] [$(#[$cfg:meta])*] [$(#[$attr:meta])*] $vis:vis $name:ident: $type:ty = $val:expr; $($rest:tt)*) => {
+		$(#[$cfg])*
+		$(#[$attr])*
+		$vis struct $name;
+		$(#[$cfg])*
+		const _: () = {
+			$crate::__internal_inner_slazy!($vis $name, $type, $val $(, $wait)?);
+		};
+		$crate::slazy!($($rest)*);
+	};
+	(@entry [$($wait:ty)?] [] [] [$(#[$cfg:meta])*] [$(#[$attr:meta])*] $vis:vis $name:ident: $type:ty = $val:expr) => {
+		$(#[$cfg])*
+		$(#[$attr])*
+		$vis struct $name;
+		$(#[$cfg])*
+		const _: () = {
+			$crate::__internal_inner_slazy!($vis $name, $type, $val $(, $wait)?);
+		};
+	};
+
+	// Common mistakes. Valid declarations never get this far.
+	(@entry $wait:tt $retry:tt $task:tt $cfg:tt $attr:tt $vis:vis static $($rest:tt)*) => {
+		::core::compile_error!("don't write `static` in `slazy!`, declare lazy statics as `NAME: Type = value;`");
+	};
+	(@entry $wait:tt $retry:tt $task:tt $cfg:tt $attr:tt $vis:vis $name:ident = $($rest:tt)*) => {
+		::core::compile_error!(::core::concat!(
+			"`", ::core::stringify!($name), "` needs a type, like `", ::core::stringify!($name), ": Type = value;`"
+		));
+	};
+	(@entry $wait:tt $retry:tt $task:tt $cfg:tt $attr:tt $vis:vis $kind:ident $name:ident = $($rest:tt)*) => {
+		::core::compile_error!(::core::concat!(
+			"`", ::core::stringify!($name), "` needs a type, like `",
+			::core::stringify!($kind), " ", ::core::stringify!($name), ": Type = value;`"
+		));
+	};
+	(@entry $wait:tt $retry:tt $task:tt $cfg:tt $attr:tt $vis:vis $name:ident: $type:ty : $($rest:tt)*) => {
+		::core::compile_error!(::core::concat!(
+			"expected `=` before the initializer of `", ::core::stringify!($name), "`, found `:`"
+		));
+	};
+	(@entry $wait:tt $retry:tt $task:tt $cfg:tt $attr:tt $vis:vis $kind:ident $name:ident: $type:ty : $($rest:tt)*) => {
+		::core::compile_error!(::core::concat!(
+			"expected `=` before the initializer of `", ::core::stringify!($name), "`, found `:`"
+		));
+	};
+	(@entry $($rest:tt)*) => {
+		::core::compile_error!("expected lazy statics declared as `NAME: Type = value;`");
+	};
+
+	($($entry:tt)+) => {
+		$crate::slazy!(@entry [] [] [] [] [] $($entry)+);
+	};
 }
 
 #[macro_export]
 #[doc(hidden)]
 macro_rules! __internal_inner_slazy {
-	($name:ident, $type:ty, $val:expr) => {
+	($vis:vis $name:ident, $type:ty, $val:expr) => {
+		$crate::__internal_inner_slazy!($vis $name, $type, $val, $crate::wait::DefaultWait);
+	};
+	($vis:vis $name:ident, $type:ty, $val:expr, $wait:ty) => {
+		$crate::__internal_inner_slazy!(@cell $vis $name, $crate::LazyCell<$type>);
+		$crate::__internal_inner_slazy!(@wait $vis $name);
+
 		impl ::core::ops::Deref for $name {
 			type Target = $type;
 
 			#[inline(always)]
 			fn deref(&self) -> &'static Self::Target {
-				static mut VAL: Option<$type> = None;
-				unsafe { VAL.get_or_insert_with(|| $val) }
+				if let ::core::option::Option::Some(value) = $crate::__override(Self::__cell()) {
+					return value;
+				}
+
+				Self::__cell().get_or_init::<$wait>(&Self::__SITE, || $val)
 			}
 		}
+
+		$crate::__slazy_if_testing! {
+			impl $name {
+				/// Makes the static give `value` on the current thread while `f`
+				/// runs. Other threads keep seeing the real value.
+				///
+				/// `value` is never dropped.
+				$vis fn with_override<R>(value: $type, f: impl ::core::ops::FnOnce() -> R) -> R {
+					$crate::testing::__with_override(Self::__cell(), value, f)
+				}
+			}
+		}
+
+		impl $name {
+			/// Returns the value if it's there, without running the
+			/// initializer.
+			#[inline]
+			$vis fn try_get(&self) -> ::core::option::Option<&'static $type> {
+				$crate::__override(Self::__cell()).or_else(|| Self::__cell().get())
+			}
+
+			/// Sets the value if nobody has accessed it yet, so the initializer
+			/// never runs. Otherwise gives `value` back.
+			#[inline]
+			$vis fn set(&self, value: $type) -> ::core::result::Result<(), $type> {
+				Self::__cell().set(value)
+			}
+		}
+	};
+	(retry $vis:vis $name:ident, $type:ty, $val:expr, $retry:expr) => {
+		$crate::__internal_inner_slazy!(
+			retry $vis $name, $type, $val, $retry, $crate::wait::DefaultWait
+		);
+	};
+	(retry $vis:vis $name:ident, $type:ty, $val:expr, $retry:expr, $wait:ty) => {
+		$crate::__internal_inner_slazy!(
+			@cell $vis $name,
+			$crate::RetryCell<
+				<$type as $crate::Fallible>::Ok,
+				<$type as $crate::Fallible>::Err,
+			>
+		);
+		$crate::__internal_inner_slazy!(@wait $vis $name);
+
+		impl $name {
+			/// Returns the value, or the error the initializer failed with.
+			#[inline]
+			$vis fn get(
+				&self,
+			) -> ::core::result::Result<
+				&'static <$type as $crate::Fallible>::Ok,
+				$crate::InitError<<$type as $crate::Fallible>::Err>,
+			> {
+				Self::__cell().get_or_init::<$wait>(&Self::__SITE, $retry, || $val)
+			}
+
+			/// Like [`get`](Self::get), but returns `None` instead of running
+			/// the initializer.
+			#[inline]
+			$vis fn try_get(
+				&self,
+			) -> ::core::option::Option<
+				::core::result::Result<
+					&'static <$type as $crate::Fallible>::Ok,
+					$crate::InitError<<$type as $crate::Fallible>::Err>,
+				>,
+			> {
+				Self::__cell().get()
+			}
+		}
+
+		$crate::__internal_inner_slazy!(@deref_result $name, $type);
+	};
+	(try $vis:vis $name:ident, $type:ty, $val:expr) => {
+		$crate::__internal_inner_slazy!(try $vis $name, $type, $val, $crate::wait::DefaultWait);
+	};
+	(try $vis:vis $name:ident, $type:ty, $val:expr, $wait:ty) => {
+		$crate::__internal_inner_slazy!(@cell $vis $name, $crate::LazyCell<$type>);
+		$crate::__internal_inner_slazy!(@wait $vis $name);
+
+		impl $name {
+			/// Returns the value, or the error the initializer failed with.
+			#[inline]
+			$vis fn get(
+				&self,
+			) -> ::core::result::Result<
+				&'static <$type as $crate::Fallible>::Ok,
+				&'static <$type as $crate::Fallible>::Err,
+			> {
+				$crate::Fallible::as_result(
+					Self::__cell().get_or_init::<$wait>(&Self::__SITE, || $val),
+				)
+			}
+
+			/// Like [`get`](Self::get), but returns `None` instead of running
+			/// the initializer.
+			#[inline]
+			$vis fn try_get(
+				&self,
+			) -> ::core::option::Option<
+				::core::result::Result<
+					&'static <$type as $crate::Fallible>::Ok,
+					&'static <$type as $crate::Fallible>::Err,
+				>,
+			> {
+				Self::__cell().get().map($crate::Fallible::as_result)
+			}
+		}
+
+		$crate::__internal_inner_slazy!(@deref_result $name, $type);
+	};
+
+	(mut $vis:vis $name:ident, $type:ty, $val:expr) => {
+		$crate::__internal_inner_slazy!(mut $vis $name, $type, $val, $crate::wait::DefaultWait);
+	};
+	(mut $vis:vis $name:ident, $type:ty, $val:expr, $wait:ty) => {
+		$crate::__internal_inner_slazy!(@cell $vis $name, $crate::LazyCell<$crate::Mutex<$type>>);
+		$crate::__internal_inner_slazy!(@wait $vis $name);
+
+		impl $name {
+			/// Locks the value, creating it first if nobody has yet.
+			#[inline]
+			$vis fn lock(&self) -> $crate::MutexGuard<'static, $type> {
+				Self::__cell()
+					.get_or_init::<$wait>(&Self::__SITE, || $crate::Mutex::new($val))
+					.lock()
+			}
+
+			/// Runs `f` with the value locked.
+			#[inline]
+			$vis fn with_mut<R>(&self, f: impl ::core::ops::FnOnce(&mut $type) -> R) -> R {
+				f(&mut self.lock())
+			}
+
+			/// Returns the mutex if the value is there, without running the
+			/// initializer.
+			#[inline]
+			$vis fn try_get(&self) -> ::core::option::Option<&'static $crate::Mutex<$type>> {
+				Self::__cell().get()
+			}
+
+			/// Sets the value if nobody has accessed it yet, so the initializer
+			/// never runs. Otherwise gives `value` back.
+			#[inline]
+			$vis fn set(&self, value: $type) -> ::core::result::Result<(), $type> {
+				Self::__cell()
+					.set($crate::Mutex::new(value))
+					.map_err($crate::Mutex::into_inner)
+			}
+		}
+	};
+	(rw $vis:vis $name:ident, $type:ty, $val:expr) => {
+		$crate::__internal_inner_slazy!(rw $vis $name, $type, $val, $crate::wait::DefaultWait);
+	};
+	(rw $vis:vis $name:ident, $type:ty, $val:expr, $wait:ty) => {
+		$crate::__internal_inner_slazy!(@cell $vis $name, $crate::LazyCell<$crate::RwLock<$type>>);
+		$crate::__internal_inner_slazy!(@wait $vis $name);
+
+		impl $name {
+			#[doc(hidden)]
+			#[inline(always)]
+			fn __lock() -> &'static $crate::RwLock<$type> {
+				Self::__cell().get_or_init::<$wait>(&Self::__SITE, || $crate::RwLock::new($val))
+			}
+
+			/// Locks the value for reading, creating it first if nobody has yet.
+			#[inline]
+			$vis fn read(&self) -> $crate::RwLockReadGuard<'static, $type> {
+				Self::__lock().read()
+			}
+
+			/// Locks the value for writing, creating it first if nobody has yet.
+			#[inline]
+			$vis fn write(&self) -> $crate::RwLockWriteGuard<'static, $type> {
+				Self::__lock().write()
+			}
+
+			/// Returns the lock if the value is there, without running the
+			/// initializer.
+			#[inline]
+			$vis fn try_get(&self) -> ::core::option::Option<&'static $crate::RwLock<$type>> {
+				Self::__cell().get()
+			}
+
+			/// Sets the value if nobody has accessed it yet, so the initializer
+			/// never runs. Otherwise gives `value` back.
+			#[inline]
+			$vis fn set(&self, value: $type) -> ::core::result::Result<(), $type> {
+				Self::__cell()
+					.set($crate::RwLock::new(value))
+					.map_err($crate::RwLock::into_inner)
+			}
+		}
+	};
+	(reload $vis:vis $name:ident, $type:ty, $val:expr) => {
+		$crate::__internal_inner_slazy!(reload $vis $name, $type, $val, $crate::wait::DefaultWait);
+	};
+	(reload $vis:vis $name:ident, $type:ty, $val:expr, $wait:ty) => {
+		$crate::__internal_inner_slazy!(@cell $vis $name, $crate::reload::Reloadable<$type>);
+		$crate::__internal_inner_slazy!(@wait $vis $name);
+
+		impl $name {
+			#[doc(hidden)]
+			#[inline(always)]
+			fn __init() -> $type {
+				$val
+			}
+
+			/// Returns a snapshot of the current value, creating it first if
+			/// nobody has yet.
+			#[inline]
+			$vis fn load(&self) -> $crate::reload::Arc<$type> {
+				Self::__cell().load::<$wait>(&Self::__SITE, Self::__init)
+			}
+
+			/// Returns a snapshot of the current value if there is one, without
+			/// running the initializer.
+			#[inline]
+			$vis fn try_get(&self) -> ::core::option::Option<$crate::reload::Arc<$type>> {
+				Self::__cell().get()
+			}
+
+			/// Replaces the value. Snapshots taken before keep the old one.
+			#[inline]
+			$vis fn store(&self, value: $type) {
+				Self::__cell().store::<$wait>(&Self::__SITE, value)
+			}
+
+			/// Runs the initializer again and stores the result.
+			#[inline]
+			$vis fn reload(&self) {
+				Self::__cell().reload::<$wait>(&Self::__SITE, Self::__init)
+			}
+
+			/// How many times the value has been stored or reloaded.
+			#[inline]
+			$vis fn generation(&self) -> usize {
+				Self::__cell().generation()
+			}
+
+			/// Calls `f` with the new generation every time the value is
+			/// stored or reloaded.
+			#[inline]
+			$vis fn subscribe(
+				&self,
+				f: impl ::core::ops::Fn(usize) + ::core::marker::Send + ::core::marker::Sync + 'static,
+			) {
+				Self::__cell().subscribe(f)
+			}
+
+			/// Blocks until the generation is different from `generation`,
+			/// and returns the new one.
+			#[inline]
+			$vis fn wait_for_change(&self, generation: usize) -> usize {
+				Self::__cell().wait_for_change::<$wait>(generation)
+			}
+		}
+
+		impl $crate::reload::Reload for $name {
+			#[inline]
+			fn name(&self) -> &'static str {
+				Self::__SITE.name
+			}
+
+			#[inline]
+			fn reload(&self) {
+				$name::reload(self)
+			}
+		}
+	};
+	(derive $vis:vis $name:ident, $type:ty, $input:ident, $f:expr) => {
+		$crate::__internal_inner_slazy!(derive $vis $name, $type, $input, $f, $crate::wait::DefaultWait);
+	};
+	(derive $vis:vis $name:ident, $type:ty, $input:ident, $f:expr, $wait:ty) => {
+		$crate::__internal_inner_slazy!(@cell $vis $name, $crate::reload::Derived<$type>);
+		$crate::__internal_inner_slazy!(@wait $vis $name);
+
+		impl $name {
+			/// Returns a snapshot of the value, computing it again first if
+			/// the input changed since the last time.
+			#[inline]
+			$vis fn load(&self) -> $crate::reload::Arc<$type> {
+				Self::__cell().load::<$wait>(
+					&Self::__SITE,
+					|| $input.generation(),
+					|| $crate::reload::__derive(&*$input.load(), $f),
+				)
+			}
+
+			/// Returns a snapshot of the value if it was computed before,
+			/// without computing it again even if the input changed.
+			#[inline]
+			$vis fn try_get(&self) -> ::core::option::Option<$crate::reload::Arc<$type>> {
+				Self::__cell().get()
+			}
+		}
+	};
+	(per_task $vis:vis $name:ident, $type:ty, $val:expr, $task:ty, $slots:expr) => {
+		$crate::__internal_inner_slazy!(
+			per_task $vis $name, $type, $val, $task, $slots, $crate::wait::DefaultWait
+		);
+	};
+	(per_task $vis:vis $name:ident, $type:ty, $val:expr, $task:ty, $slots:expr, $wait:ty) => {
+		$crate::__internal_inner_slazy!(@cell $vis $name, $crate::per_task::PerTask<$type, $task, { $slots }>);
+
+		impl ::core::ops::Deref for $name {
+			type Target = $type;
+
+			/// Returns the current task's value.
+			#[inline(always)]
+			fn deref(&self) -> &'static Self::Target {
+				Self::__cell().get_or_init::<$wait>(&Self::__SITE, || $val)
+			}
+		}
+
+		impl $name {
+			/// Returns the current task's value if it has one, without running
+			/// the initializer.
+			#[inline]
+			$vis fn try_get(&self) -> ::core::option::Option<&'static $type> {
+				Self::__cell().get()
+			}
+		}
+	};
+	(local $vis:vis $name:ident, $type:ty, $val:expr) => {
+		impl $name {
+			/// Runs `f` with the current thread's value, creating it first if
+			/// this thread hasn't used it yet.
+			#[inline]
+			$vis fn with<R>(&self, f: impl ::core::ops::FnOnce(&$type) -> R) -> R {
+				f(&**self)
+			}
+		}
+
+		impl ::core::ops::Deref for $name {
+			type Target = $type;
+
+			#[inline(always)]
+			fn deref(&self) -> &'static Self::Target {
+				// Leaking the value is what makes the reference `'static`: it
+				// can't dangle when the thread exits.
+				$crate::__thread_local! {
+					static VALUE: &'static $type = $crate::__leak($val);
+				}
+
+				VALUE.with(|value| *value)
+			}
+		}
+	};
+
+	// The storage, and everything generated the same way for every kind of lazy.
+	(@cell $vis:vis $name:ident, $cell:ty) => {
+		impl $name {
+			#[doc(hidden)]
+			const __SITE: $crate::Site = $crate::Site {
+				name: ::core::stringify!($name),
+				file: ::core::file!(),
+				line: ::core::line!(),
+			};
+
+			#[doc(hidden)]
+			#[inline(always)]
+			fn __cell() -> &'static $cell {
+				static CELL: $cell = <$cell>::new();
+				&CELL
+			}
+
+			/// Whether the value is there, without running the initializer.
+			#[inline]
+			$vis fn is_initialized(&self) -> bool {
+				Self::__cell().is_initialized()
+			}
+
+			/// Whether the initializer panicked. Accessing a poisoned lazy static
+			/// panics too, until [`clear_poison`](Self::clear_poison) is called.
+			#[inline]
+			$vis fn is_poisoned(&self) -> bool {
+				Self::__cell().is_poisoned()
+			}
+
+			/// Lets the next access run the initializer again after it panicked.
+			#[inline]
+			$vis fn clear_poison(&self) {
+				Self::__cell().clear_poison()
+			}
+		}
+
+		$crate::__slazy_if_testing! {
+			impl $name {
+				/// Drops the value, so the next access runs the initializer again.
+				/// Also clears the poison.
+				///
+				/// # Safety
+				///
+				/// No references to the value (from `Deref`, `get()` or a lock
+				/// guard) can be alive, and nobody can be using it at the same
+				/// time.
+				///
+				/// # Panics
+				///
+				/// If the initializer is running.
+				$vis unsafe fn reset(&self) {
+					// SAFETY: guaranteed by the caller.
+					unsafe { Self::__cell().reset(&Self::__SITE) }
+				}
+			}
+
+			impl $crate::testing::Reset for $name {
+				unsafe fn reset(&self) {
+					// SAFETY: guaranteed by the caller.
+					unsafe { $name::reset(self) }
+				}
+			}
+		}
+	};
+
+	// Per-task values can only be initialized by their own task, so waiting
+	// for someone else to do it makes no sense for them.
+	(@wait $vis:vis $name:ident) => {
+		$crate::__slazy_if_std! {
+			impl $name {
+				/// Blocks until someone else initializes the value. Never runs
+				/// the initializer.
+				///
+				/// # Panics
+				///
+				/// If the initializer panics, or already did.
+				#[inline]
+				$vis fn wait(&self) {
+					$crate::wait::__wait_for_init(
+						&Self::__SITE,
+						::core::option::Option::None,
+						|| self.is_initialized(),
+						|| self.is_poisoned(),
+					);
+				}
+
+				/// Like [`wait`](Self::wait), but gives up after `timeout`.
+				/// Returns whether the value is there.
+				#[inline]
+				$vis fn wait_timeout(&self, timeout: ::core::time::Duration) -> bool {
+					$crate::wait::__wait_for_init(
+						&Self::__SITE,
+						::core::option::Option::Some(timeout),
+						|| self.is_initialized(),
+						|| self.is_poisoned(),
+					)
+				}
+			}
+		}
+	};
+
+	// `Deref` for fallible lazies, in terms of their `get`.
+	(@deref_result $name:ident, $type:ty) => {
+		impl ::core::ops::Deref for $name {
+			type Target = <$type as $crate::Fallible>::Ok;
+
+			#[inline(always)]
+			fn deref(&self) -> &'static Self::Target {
+				match self.get() {
+					::core::result::Result::Ok(value) => value,
+					::core::result::Result::Err(err) => ::core::panic!(
+						"slazy: {} failed to initialize: {:?}",
+						Self::__SITE,
+						err,
+					),
+				}
+			}
+		}
+	};
+}
+
+/// Expands to its input only with the `std` feature.
+#[cfg(feature = "std")]
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __slazy_if_std {
+	($($item:item)*) => {
+		$($item)*
+	};
+}
+
+/// Expands to its input only with the `std` feature.
+#[cfg(not(feature = "std"))]
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __slazy_if_std {
+	($($item:item)*) => {};
+}
+
+/// Expands to its input only with the `testing` feature.
+#[cfg(feature = "testing")]
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __slazy_if_testing {
+	($($item:item)*) => {
+		$($item)*
+	};
+}
+
+/// Expands to its input only with the `testing` feature.
+#[cfg(not(feature = "testing"))]
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __slazy_if_testing {
+	($($item:item)*) => {};
+}
+
+/// Like [`slazy!`], but every thread gets its own value, created the first
+/// time that thread uses it. The type doesn't need to be `Send` or `Sync`.
+///
+/// Access it with `with(|value| ...)` or by dereferencing it, which always
+/// gives you the current thread's value.
+///
+/// Only available with the `std` feature.
+///
+/// ```
+/// use slazy::slazy_thread_local;
+/// use std::cell::Cell;
+///
+/// slazy_thread_local! {
+///     pub COUNTER: Cell<u32> = Cell::new(0);
+/// }
+///
+/// COUNTER.set(COUNTER.get() + 1);
+/// COUNTER.with(|counter| assert_eq!(counter.get(), 1));
+///
+/// std::thread::spawn(|| assert_eq!(COUNTER.get(), 0)).join().unwrap();
+/// ```
+///
+/// Values live for as long as the program does, even after their thread
+/// exits, so they are never dropped. Keep that in mind if you spawn lots of
+/// short-lived threads.
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! slazy_thread_local {
+	($vis:vis $name:ident: $type:ty = $val:expr; $($rest:tt)*) => {
+		$vis struct $name;
+		$crate::__internal_inner_slazy!(local $vis $name, $type, $val);
+		$crate::slazy_thread_local!($($rest)*);
+	};
+	($vis:vis $name:ident: $type:ty = $val:expr) => {
+		$vis struct $name;
+		$crate::__internal_inner_slazy!(local $vis $name, $type, $val);
 	};
+	() => {};
+}
+
+/// Moves `value` to the heap and never frees it.
+#[cfg(feature = "std")]
+#[doc(hidden)]
+#[inline]
+pub fn __leak<T>(value: T) -> &'static T {
+	std::boxed::Box::leak(std::boxed::Box::new(value))
+}
+
+/// The value `cell`'s static is overridden with on the current thread, if
+/// any. Always `None` without the `testing` feature.
+#[doc(hidden)]
+#[inline(always)]
+pub fn __override<T: 'static, C>(cell: &'static C) -> Option<&'static T> {
+	#[cfg(feature = "testing")]
+	return testing::get(cell as *const C as *const ());
+
+	#[cfg(not(feature = "testing"))]
+	{
+		_ = cell;
+		None
+	}
 }
 
-/// This macro is used to initialize lazy statics, which
-/// is required for them to be safe in multithreaded environments.
+/// This macro is used to initialize lazy statics ahead of time,
+/// so the first real access doesn't pay for the initializer.
 ///
 /// Equivalent to `_ = *(your lazy static);`;
 #[macro_export]
@@ -0,0 +1,232 @@
+//! The lock behind `mut` lazy statics.
+
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+
+#[cfg(not(feature = "std"))]
+use core::{
+	cell::UnsafeCell,
+	marker::PhantomData,
+	sync::atomic::{AtomicBool, Ordering},
+};
+
+/// A mutual exclusion lock: `std::sync::Mutex` with the `std` feature, a
+/// spin lock without it.
+///
+/// Unlike the one in `std`, it doesn't get poisoned when a thread panics
+/// while holding it.
+///
+/// Formatting it with `{:?}` while it's locked doesn't wait for it:
+///
+/// ```
+/// let mutex = slazy::Mutex::new(1);
+/// let guard = mutex.lock();
+///
+/// assert_eq!(format!("{mutex:?}"), "Mutex { value: <locked> }");
+/// drop(guard);
+/// assert_eq!(format!("{mutex:?}"), "Mutex { value: 1 }");
+/// ```
+pub struct Mutex<T: ?Sized> {
+	#[cfg(feature = "std")]
+	inner: std::sync::Mutex<T>,
+	#[cfg(not(feature = "std"))]
+	locked: AtomicBool,
+	#[cfg(not(feature = "std"))]
+	value: UnsafeCell<T>,
+}
+
+#[cfg(not(feature = "std"))]
+unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {}
+#[cfg(not(feature = "std"))]
+unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
+
+/// Gives access to the value in a [`Mutex`], and unlocks it when dropped.
+///
+/// It can't be sent to another thread, with or without `std`:
+///
+/// ```compile_fail
+/// fn assert_send<T: Send>(_: T) {}
+///
+/// let mutex = slazy::Mutex::new(0);
+/// assert_send(mutex.lock());
+/// ```
+pub struct MutexGuard<'a, T: ?Sized> {
+	#[cfg(feature = "std")]
+	inner: std::sync::MutexGuard<'a, T>,
+	#[cfg(not(feature = "std"))]
+	mutex: &'a Mutex<T>,
+	/// Keeps it `!Send` like the one in `std`, so turning on the `std`
+	/// feature can't break code that compiled without it.
+	#[cfg(not(feature = "std"))]
+	_not_send: PhantomData<*const ()>,
+}
+
+#[cfg(not(feature = "std"))]
+unsafe impl<T: ?Sized + Sync> Sync for MutexGuard<'_, T> {}
+
+impl<T> Mutex<T> {
+	/// Creates an unlocked mutex.
+	#[inline]
+	pub const fn new(value: T) -> Self {
+		Self {
+			#[cfg(feature = "std")]
+			inner: std::sync::Mutex::new(value),
+			#[cfg(not(feature = "std"))]
+			locked: AtomicBool::new(false),
+			#[cfg(not(feature = "std"))]
+			value: UnsafeCell::new(value),
+		}
+	}
+
+	/// Takes the value out of the mutex.
+	#[inline]
+	pub fn into_inner(self) -> T {
+		#[cfg(feature = "std")]
+		return self
+			.inner
+			.into_inner()
+			.unwrap_or_else(std::sync::PoisonError::into_inner);
+
+		#[cfg(not(feature = "std"))]
+		self.value.into_inner()
+	}
+}
+
+impl<T: ?Sized> Mutex<T> {
+	/// Waits until the lock is free, then takes it.
+	#[inline]
+	pub fn lock(&self) -> MutexGuard<'_, T> {
+		#[cfg(feature = "std")]
+		return MutexGuard {
+			inner: self
+				.inner
+				.lock()
+				.unwrap_or_else(std::sync::PoisonError::into_inner),
+		};
+
+		#[cfg(not(feature = "std"))]
+		{
+			while !self.try_acquire() {
+				while self.locked.load(Ordering::Relaxed) {
+					core::hint::spin_loop();
+				}
+			}
+
+			self.guard()
+		}
+	}
+
+	/// Takes the lock if it's free, without waiting.
+	#[inline]
+	fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+		#[cfg(feature = "std")]
+		return match self.inner.try_lock() {
+			Ok(inner) => Some(MutexGuard { inner }),
+			Err(std::sync::TryLockError::Poisoned(err)) => Some(MutexGuard {
+				inner: err.into_inner(),
+			}),
+			Err(std::sync::TryLockError::WouldBlock) => None,
+		};
+
+		#[cfg(not(feature = "std"))]
+		self.try_acquire().then(|| self.guard())
+	}
+}
+
+#[cfg(not(feature = "std"))]
+impl<T: ?Sized> Mutex<T> {
+	/// The guard for a lock we just took.
+	#[inline]
+	fn guard(&self) -> MutexGuard<'_, T> {
+		MutexGuard {
+			mutex: self,
+			_not_send: PhantomData,
+		}
+	}
+
+	/// Takes the lock if it's free.
+	#[inline]
+	#[cfg(not(feature = "critical-section"))]
+	fn try_acquire(&self) -> bool {
+		self.locked
+			.compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+			.is_ok()
+	}
+
+	/// Takes the lock if it's free. Uses a critical section instead of a CAS,
+	/// which some targets don't have.
+	#[cfg(feature = "critical-section")]
+	fn try_acquire(&self) -> bool {
+		critical_section::with(|_| {
+			if self.locked.load(Ordering::Acquire) {
+				return false;
+			}
+
+			self.locked.store(true, Ordering::Relaxed);
+			true
+		})
+	}
+}
+
+impl<T: Default> Default for Mutex<T> {
+	fn default() -> Self {
+		Self::new(T::default())
+	}
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Mutex<T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		// Locking would never finish if this thread already holds it.
+		match self.try_lock() {
+			Some(guard) => f.debug_struct("Mutex").field("value", &&*guard).finish(),
+			None => f
+				.debug_struct("Mutex")
+				.field("value", &format_args!("<locked>"))
+				.finish(),
+		}
+	}
+}
+
+impl<T: ?Sized> Deref for MutexGuard<'_, T> {
+	type Target = T;
+
+	#[inline]
+	fn deref(&self) -> &T {
+		#[cfg(feature = "std")]
+		return &self.inner;
+
+		// SAFETY: we hold the lock.
+		#[cfg(not(feature = "std"))]
+		unsafe {
+			&*self.mutex.value.get()
+		}
+	}
+}
+
+impl<T: ?Sized> DerefMut for MutexGuard<'_, T> {
+	#[inline]
+	fn deref_mut(&mut self) -> &mut T {
+		#[cfg(feature = "std")]
+		return &mut self.inner;
+
+		// SAFETY: we hold the lock.
+		#[cfg(not(feature = "std"))]
+		unsafe {
+			&mut *self.mutex.value.get()
+		}
+	}
+}
+
+#[cfg(not(feature = "std"))]
+impl<T: ?Sized> Drop for MutexGuard<'_, T> {
+	#[inline]
+	fn drop(&mut self) {
+		self.mutex.locked.store(false, Ordering::Release);
+	}
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for MutexGuard<'_, T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		(**self).fmt(f)
+	}
+}
@@ -0,0 +1,188 @@
+//! Lazy statics with one value per task, for targets without `std`.
+//!
+//! Add `#[per_task(Provider, N)]` to a static to give every task its own
+//! value, created the first time that task uses it. `Provider` tells slazy
+//! which task is running (see [`ThreadIdProvider`]), and `N` is how many
+//! tasks can have a value at once.
+//!
+//! ```
+//! use slazy::{slazy, per_task::ThreadIdProvider};
+//! use std::cell::Cell;
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//!
+//! struct Tasks;
+//!
+//! // SAFETY: every thread gets a different number.
+//! unsafe impl ThreadIdProvider for Tasks {
+//!     fn current_id() -> usize {
+//!         static NEXT: AtomicUsize = AtomicUsize::new(0);
+//!
+//!         std::thread_local! {
+//!             static ID: usize = NEXT.fetch_add(1, Ordering::Relaxed);
+//!         }
+//!
+//!         ID.with(|id| *id)
+//!     }
+//! }
+//!
+//! slazy! {
+//!     #[per_task(Tasks, 8)]
+//!     pub SCRATCH: Cell<u32> = Cell::new(0);
+//! }
+//!
+//! SCRATCH.set(5);
+//! std::thread::spawn(|| assert_eq!(SCRATCH.get(), 0)).join().unwrap();
+//! assert_eq!(SCRATCH.get(), 5);
+//! ```
+
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::wait::WaitStrategy;
+use crate::{LazyCell, Site};
+
+/// Tells per-task lazy statics which task (or thread) is running.
+///
+/// # Safety
+///
+/// Two tasks that are alive at the same time must never get the same id,
+/// and a task must always get the same one. Ids can be reused once their
+/// task is gone: the next task with that id will see its value. `usize::MAX`
+/// can't be used as an id.
+pub unsafe trait ThreadIdProvider {
+	/// The id of the current task.
+	fn current_id() -> usize;
+}
+
+/// Marks a slot nobody has claimed.
+const FREE: usize = usize::MAX;
+
+/// The storage behind a per-task lazy static: `N` slots, each claimed by the
+/// first task that needs one.
+pub struct PerTask<T, P, const N: usize> {
+	ids: [AtomicUsize; N],
+	cells: [LazyCell<T>; N],
+	provider: PhantomData<fn() -> P>,
+}
+
+// Only the task that claimed a slot touches its value (references to it can
+// only leave that task if `T: Sync`), but ids get reused, so the value can
+// change hands between tasks.
+unsafe impl<T: Send, P, const N: usize> Sync for PerTask<T, P, N> {}
+
+impl<T, P: ThreadIdProvider, const N: usize> PerTask<T, P, N> {
+	/// Creates a storage with no slots claimed.
+	#[inline]
+	pub const fn new() -> Self {
+		Self {
+			ids: [const { AtomicUsize::new(FREE) }; N],
+			cells: [const { LazyCell::new() }; N],
+			provider: PhantomData,
+		}
+	}
+
+	/// Returns the current task's value, running `f` first if this task
+	/// hasn't used it yet.
+	///
+	/// # Panics
+	///
+	/// If all the slots are taken by other tasks, or the current task's value
+	/// is poisoned.
+	#[inline]
+	pub fn get_or_init<W: WaitStrategy>(&self, site: &'static Site, f: impl FnOnce() -> T) -> &T {
+		self.slot(site).get_or_init::<W>(site, f)
+	}
+
+	/// Returns the current task's value, without running the initializer.
+	#[inline]
+	pub fn get(&self) -> Option<&T> {
+		self.find(P::current_id())?.get()
+	}
+
+	/// Whether the current task has its value.
+	#[inline]
+	pub fn is_initialized(&self) -> bool {
+		self.get().is_some()
+	}
+
+	/// Whether the current task's initializer panicked.
+	#[inline]
+	pub fn is_poisoned(&self) -> bool {
+		self.find(P::current_id())
+			.is_some_and(|cell| cell.is_poisoned())
+	}
+
+	/// Lets the current task run the initializer again after it panicked.
+	#[inline]
+	pub fn clear_poison(&self) {
+		if let Some(cell) = self.find(P::current_id()) {
+			cell.clear_poison();
+		}
+	}
+
+	/// Drops every task's value and frees all the slots.
+	///
+	/// # Safety
+	///
+	/// No references to any of the values can be alive.
+	#[cfg(feature = "testing")]
+	pub unsafe fn reset(&self, site: &'static Site) {
+		for (slot, cell) in self.ids.iter().zip(&self.cells) {
+			cell.reset(site);
+			slot.store(FREE, Ordering::Relaxed);
+		}
+	}
+
+	/// The current task's slot, claiming a free one if it doesn't have one yet.
+	fn slot(&self, site: &'static Site) -> &LazyCell<T> {
+		let id = P::current_id();
+
+		if let Some(cell) = self.find(id) {
+			return cell;
+		}
+
+		for (slot, cell) in self.ids.iter().zip(&self.cells) {
+			if claim(slot, id) {
+				return cell;
+			}
+		}
+
+		panic!("slazy: {site} has no free slot left for another task (it has {N})")
+	}
+
+	#[inline]
+	fn find(&self, id: usize) -> Option<&LazyCell<T>> {
+		self.ids
+			.iter()
+			.position(|slot| slot.load(Ordering::Relaxed) == id)
+			.map(|slot| &self.cells[slot])
+	}
+}
+
+/// Gives `slot` to `id` if nobody has claimed it.
+#[inline]
+#[cfg(not(feature = "critical-section"))]
+fn claim(slot: &AtomicUsize, id: usize) -> bool {
+	slot.compare_exchange(FREE, id, Ordering::Relaxed, Ordering::Relaxed)
+		.is_ok()
+}
+
+/// Gives `slot` to `id` if nobody has claimed it. Uses a critical section
+/// instead of a CAS, which some targets don't have.
+#[cfg(feature = "critical-section")]
+fn claim(slot: &AtomicUsize, id: usize) -> bool {
+	critical_section::with(|_| {
+		if slot.load(Ordering::Relaxed) != FREE {
+			return false;
+		}
+
+		slot.store(id, Ordering::Relaxed);
+		true
+	})
+}
+
+impl<T, P: ThreadIdProvider, const N: usize> Default for PerTask<T, P, N> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
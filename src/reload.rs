@@ -0,0 +1,427 @@
+//! Lazy statics whose value can be replaced at runtime.
+//!
+//! Put `reload` in front of a static's name to make it reloadable. Its value
+//! is still created lazily, but instead of `Deref` it gets:
+//!
+//! - `load()`, which returns a snapshot of the current value as an [`Arc`].
+//! - `store(value)`, which replaces it.
+//! - `reload()`, which runs the initializer again and stores the result.
+//!
+//! To find out when the value changes, there's also:
+//!
+//! - `generation()`, which counts how many times it has been stored or
+//!   reloaded.
+//! - `subscribe(callback)`, which calls `callback` with the new generation
+//!   after every store or reload.
+//! - `wait_for_change(generation)`, which blocks until the generation is
+//!   different from the one passed in, and returns the new one.
+//!
+//! Readers never block: replacing the value only makes the writer wait until
+//! nobody is in the middle of taking a snapshot of the old one. Snapshots
+//! taken before that keep the old value alive for as long as they need it.
+//!
+//! Only available with the `alloc` feature.
+//!
+//! ```
+//! use slazy::slazy;
+//!
+//! slazy! {
+//!     pub reload CONFIG: String = String::from("v1");
+//! }
+//!
+//! let before = CONFIG.load();
+//! CONFIG.store(String::from("v2"));
+//!
+//! assert_eq!(*before, "v1");
+//! assert_eq!(*CONFIG.load(), "v2");
+//!
+//! CONFIG.reload();
+//! assert_eq!(*CONFIG.load(), "v1");
+//! assert_eq!(CONFIG.generation(), 2);
+//! ```
+//!
+//! Waiting for someone else to change the value:
+//!
+//! ```
+//! use slazy::slazy;
+//!
+//! slazy! {
+//!     pub reload LEVEL: u8 = 0;
+//! }
+//!
+//! LEVEL.subscribe(|generation| println!("log level changed ({generation})"));
+//!
+//! let seen = LEVEL.generation();
+//! let writer = std::thread::spawn(|| LEVEL.store(3));
+//!
+//! assert_eq!(LEVEL.wait_for_change(seen), seen + 1);
+//! assert_eq!(*LEVEL.load(), 3);
+//! writer.join().unwrap();
+//! ```
+//!
+//! # Derived statics
+//!
+//! A static can also be computed from a reloadable one with
+//! `derive(INPUT => |input| ...)`. The closure gets a reference to the
+//! input's current value, and runs again on the next `load()` after the input
+//! changes:
+//!
+//! ```
+//! use slazy::slazy;
+//!
+//! slazy! {
+//!     pub reload CONFIG: String = String::from("/home\n/about");
+//!     pub ROUTES: Vec<String> = derive(CONFIG => |config| {
+//!         config.lines().map(String::from).collect()
+//!     });
+//! }
+//!
+//! assert_eq!(ROUTES.load().len(), 2);
+//!
+//! CONFIG.store(String::from("/home\n/about\n/blog"));
+//! assert_eq!(ROUTES.load().len(), 3);
+//! ```
+//!
+//! The input has to be a `reload` static.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+#[doc(no_inline)]
+pub use alloc::sync::Arc;
+
+use crate::wait::{self, WaitStrategy};
+use crate::{LazyCell, Mutex, Site};
+
+type Subscriber = Box<dyn Fn(usize) + Send + Sync>;
+
+/// The storage behind a reloadable lazy static.
+///
+/// The value lives in an [`Arc`] whose pointer is swapped on every store.
+/// Readers announce themselves in one of two counters before reading the
+/// pointer, and writers flip which counter new readers use, then wait for the
+/// old one to drain before letting go of the old value. Since new readers use
+/// the other counter, a steady stream of them can't keep a writer waiting.
+pub struct Reloadable<T> {
+	init: LazyCell<()>,
+	value: AtomicPtr<T>,
+	readers: [AtomicUsize; 2],
+	epoch: AtomicUsize,
+	writer: Mutex<()>,
+	generation: AtomicUsize,
+	subscribers: Mutex<Vec<Subscriber>>,
+	/// Makes it `Send`/`Sync` only if an `Arc<T>` would be.
+	_marker: PhantomData<Arc<T>>,
+}
+
+impl<T> Reloadable<T> {
+	/// Creates an empty storage.
+	#[inline]
+	pub const fn new() -> Self {
+		Self {
+			init: LazyCell::new(),
+			value: AtomicPtr::new(ptr::null_mut()),
+			readers: [AtomicUsize::new(0), AtomicUsize::new(0)],
+			epoch: AtomicUsize::new(0),
+			writer: Mutex::new(()),
+			generation: AtomicUsize::new(0),
+			subscribers: Mutex::new(Vec::new()),
+			_marker: PhantomData,
+		}
+	}
+
+	/// Returns a snapshot of the value, running `f` first if there's no value
+	/// yet.
+	#[inline]
+	pub fn load<W: WaitStrategy>(&self, site: &'static Site, f: impl FnOnce() -> T) -> Arc<T> {
+		self.init.get_or_init::<W>(site, || {
+			self.value
+				.store(Arc::into_raw(Arc::new(f())).cast_mut(), Ordering::SeqCst);
+		});
+
+		self.snapshot()
+	}
+
+	/// Returns a snapshot of the value, without running the initializer.
+	#[inline]
+	pub fn get(&self) -> Option<Arc<T>> {
+		self.init.get()?;
+		Some(self.snapshot())
+	}
+
+	/// Whether the value is there.
+	#[inline]
+	pub fn is_initialized(&self) -> bool {
+		self.init.is_initialized()
+	}
+
+	/// Takes a reference to the current value.
+	///
+	/// The storage must be initialized.
+	fn snapshot(&self) -> Arc<T> {
+		let readers = loop {
+			let epoch = self.epoch.load(Ordering::SeqCst);
+			let readers = &self.readers[epoch & 1];
+			readers.fetch_add(1, Ordering::SeqCst);
+
+			// If a writer flipped the epoch before we were counted, it (and the
+			// writers after it) wait on the other counter, so they wouldn't
+			// wait for us. Only read the value once we're counted in the
+			// current one.
+			if self.epoch.load(Ordering::SeqCst) == epoch {
+				break readers;
+			}
+
+			readers.fetch_sub(1, Ordering::SeqCst);
+		};
+
+		let value = self.value.load(Ordering::SeqCst);
+		// SAFETY: a writer that replaced `value` after we loaded it is waiting
+		// for `readers` to reach zero before releasing its reference.
+		unsafe { Arc::increment_strong_count(value) };
+		readers.fetch_sub(1, Ordering::SeqCst);
+
+		// SAFETY: we own the reference we just added.
+		unsafe { Arc::from_raw(value) }
+	}
+
+	/// Replaces the value. If there's no value yet, the initializer will never
+	/// run.
+	pub fn store<W: WaitStrategy>(&self, site: &'static Site, value: T) {
+		let mut value = Some(value);
+
+		self.init.get_or_init::<W>(site, || {
+			let first = Arc::new(value.take().unwrap());
+			self.value
+				.store(Arc::into_raw(first).cast_mut(), Ordering::SeqCst);
+		});
+
+		if let Some(value) = value {
+			self.swap(Arc::new(value));
+		}
+
+		self.changed();
+	}
+
+	/// Runs `f` and stores its result.
+	pub fn reload<W: WaitStrategy>(&self, site: &'static Site, f: impl FnOnce() -> T) {
+		self.store::<W>(site, f());
+	}
+
+	/// How many times the value has been stored or reloaded.
+	#[inline]
+	pub fn generation(&self) -> usize {
+		self.generation.load(Ordering::SeqCst)
+	}
+
+	/// Calls `f` with the new generation every time the value is stored or
+	/// reloaded, from the thread that did it.
+	///
+	/// `f` must not subscribe again or replace this value, since that would
+	/// deadlock.
+	pub fn subscribe(&self, f: impl Fn(usize) + Send + Sync + 'static) {
+		self.subscribers.lock().push(Box::new(f));
+	}
+
+	/// Blocks until the generation is different from `generation`, and
+	/// returns the new one.
+	pub fn wait_for_change<W: WaitStrategy>(&self, generation: usize) -> usize {
+		W::wait_until(|| self.generation() != generation);
+		self.generation()
+	}
+
+	/// Whether the first initializer panicked.
+	#[inline]
+	pub fn is_poisoned(&self) -> bool {
+		self.init.is_poisoned()
+	}
+
+	/// Lets the next access run the initializer again after it panicked.
+	#[inline]
+	pub fn clear_poison(&self) {
+		self.init.clear_poison();
+	}
+
+	/// Drops the storage's reference to the value, so the next access runs the
+	/// initializer again. Snapshots keep the old value alive.
+	///
+	/// # Safety
+	///
+	/// Nobody can be using the storage at the same time.
+	#[cfg(feature = "testing")]
+	pub unsafe fn reset(&self, site: &'static Site) {
+		let value = self.value.swap(ptr::null_mut(), Ordering::SeqCst);
+
+		if !value.is_null() {
+			// SAFETY: `value` came from `Arc::into_raw`.
+			drop(Arc::from_raw(value));
+		}
+
+		self.init.reset(site);
+		self.changed();
+	}
+
+	fn swap(&self, value: Arc<T>) {
+		let _writer = self.writer.lock();
+		let old = self
+			.value
+			.swap(Arc::into_raw(value).cast_mut(), Ordering::SeqCst);
+
+		// Readers that show up from now on use the other counter, and can only
+		// see the new value. The ones counted in the old one might be about to
+		// take a reference to `old`.
+		let epoch = self.epoch.fetch_add(1, Ordering::SeqCst);
+
+		while self.readers[epoch & 1].load(Ordering::SeqCst) != 0 {
+			core::hint::spin_loop();
+		}
+
+		// SAFETY: `old` came from `Arc::into_raw`, and nobody can reach it anymore.
+		drop(unsafe { Arc::from_raw(old) });
+	}
+
+	fn changed(&self) {
+		let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+
+		for subscriber in self.subscribers.lock().iter() {
+			subscriber(generation);
+		}
+
+		wait::notify_all();
+	}
+}
+
+impl<T> Default for Reloadable<T> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<T> Drop for Reloadable<T> {
+	fn drop(&mut self) {
+		let value = *self.value.get_mut();
+
+		if !value.is_null() {
+			// SAFETY: `value` came from `Arc::into_raw`.
+			drop(unsafe { Arc::from_raw(value) });
+		}
+	}
+}
+
+/// The storage behind a derived lazy static.
+///
+/// Remembers the input's generation the value was computed from, and
+/// computes it again when the input's generation is different.
+pub struct Derived<T> {
+	value: Reloadable<T>,
+	generation: AtomicUsize,
+	/// Held while computing a new value, so an old one can't overwrite it.
+	recompute: Mutex<()>,
+}
+
+impl<T> Derived<T> {
+	/// Creates an empty storage.
+	#[inline]
+	pub const fn new() -> Self {
+		Self {
+			value: Reloadable::new(),
+			generation: AtomicUsize::new(0),
+			recompute: Mutex::new(()),
+		}
+	}
+
+	/// Returns a snapshot of the value, running `f` first if there's no value
+	/// yet or it was computed from an older `generation` of the input.
+	pub fn load<W: WaitStrategy>(
+		&self,
+		site: &'static Site,
+		generation: impl Fn() -> usize,
+		f: impl Fn() -> T,
+	) -> Arc<T> {
+		// The generation is read before `f` looks at the input, so if the input
+		// changes in between, the next load just computes the value again.
+		let value = self.value.load::<W>(site, || {
+			self.generation.store(generation(), Ordering::SeqCst);
+			f()
+		});
+
+		if self.generation.load(Ordering::SeqCst) == generation() {
+			return value;
+		}
+
+		let _recompute = self.recompute.lock();
+		let current = generation();
+
+		if self.generation.load(Ordering::SeqCst) != current {
+			self.value.store::<W>(site, f());
+			self.generation.store(current, Ordering::SeqCst);
+		}
+
+		self.value.load::<W>(site, f)
+	}
+
+	/// Returns a snapshot of the value, without computing it, even if the
+	/// input changed.
+	#[inline]
+	pub fn get(&self) -> Option<Arc<T>> {
+		self.value.get()
+	}
+
+	/// Whether the value was computed at least once.
+	#[inline]
+	pub fn is_initialized(&self) -> bool {
+		self.value.is_initialized()
+	}
+
+	/// Whether the first initializer panicked.
+	#[inline]
+	pub fn is_poisoned(&self) -> bool {
+		self.value.is_poisoned()
+	}
+
+	/// Lets the next access run the initializer again after it panicked.
+	#[inline]
+	pub fn clear_poison(&self) {
+		self.value.clear_poison();
+	}
+
+	/// Drops the storage's reference to the value, so the next access
+	/// computes it again.
+	///
+	/// # Safety
+	///
+	/// See [`Reloadable::reset`].
+	#[cfg(feature = "testing")]
+	pub unsafe fn reset(&self, site: &'static Site) {
+		self.value.reset(site);
+	}
+}
+
+impl<T> Default for Derived<T> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// Calls `f` with `input`. Lets the type of the closure's argument be
+/// inferred.
+#[doc(hidden)]
+#[inline(always)]
+pub fn __derive<I: ?Sized, T>(input: &I, f: impl FnOnce(&I) -> T) -> T {
+	f(input)
+}
+
+/// A reloadable lazy static, seen from code that doesn't know its type (like
+/// `sighup::reload_on_sighup`, with the `sighup` feature).
+///
+/// Implemented by every `reload` static.
+pub trait Reload: Sync {
+	/// The static's name.
+	fn name(&self) -> &'static str;
+
+	/// Runs the initializer again and stores the result.
+	fn reload(&self);
+}
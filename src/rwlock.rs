@@ -0,0 +1,356 @@
+//! The lock behind `rw` lazy statics.
+
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+
+#[cfg(not(feature = "std"))]
+use core::{
+	cell::UnsafeCell,
+	marker::PhantomData,
+	sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Set in `state` while a writer holds the lock. The rest of the bits count
+/// the readers.
+#[cfg(not(feature = "std"))]
+const WRITER: usize = !(usize::MAX >> 1);
+
+/// A reader-writer lock: `std::sync::RwLock` with the `std` feature, a spin
+/// lock with an atomic reader counter without it.
+///
+/// Unlike the one in `std`, it doesn't get poisoned when a thread panics
+/// while holding it.
+///
+/// Formatting it with `{:?}` while it's locked for writing doesn't wait for
+/// it:
+///
+/// ```
+/// let lock = slazy::RwLock::new(1);
+/// let guard = lock.write();
+///
+/// assert_eq!(format!("{lock:?}"), "RwLock { value: <locked> }");
+/// drop(guard);
+/// assert_eq!(format!("{lock:?}"), "RwLock { value: 1 }");
+/// ```
+pub struct RwLock<T: ?Sized> {
+	#[cfg(feature = "std")]
+	inner: std::sync::RwLock<T>,
+	#[cfg(not(feature = "std"))]
+	state: AtomicUsize,
+	#[cfg(not(feature = "std"))]
+	value: UnsafeCell<T>,
+}
+
+#[cfg(not(feature = "std"))]
+unsafe impl<T: ?Sized + Send + Sync> Sync for RwLock<T> {}
+#[cfg(not(feature = "std"))]
+unsafe impl<T: ?Sized + Send> Send for RwLock<T> {}
+
+/// Gives shared access to the value in a [`RwLock`], and unlocks it when
+/// dropped.
+///
+/// Like the write guard, it can't be sent to another thread, with or without
+/// `std`:
+///
+/// ```compile_fail
+/// fn assert_send<T: Send>(_: T) {}
+///
+/// let lock = slazy::RwLock::new(0);
+/// assert_send(lock.read());
+/// ```
+pub struct RwLockReadGuard<'a, T: ?Sized> {
+	#[cfg(feature = "std")]
+	inner: std::sync::RwLockReadGuard<'a, T>,
+	#[cfg(not(feature = "std"))]
+	lock: &'a RwLock<T>,
+	/// Keeps it `!Send` like the one in `std`, so turning on the `std`
+	/// feature can't break code that compiled without it.
+	#[cfg(not(feature = "std"))]
+	_not_send: PhantomData<*const ()>,
+}
+
+/// Gives exclusive access to the value in a [`RwLock`], and unlocks it when
+/// dropped.
+pub struct RwLockWriteGuard<'a, T: ?Sized> {
+	#[cfg(feature = "std")]
+	inner: std::sync::RwLockWriteGuard<'a, T>,
+	#[cfg(not(feature = "std"))]
+	lock: &'a RwLock<T>,
+	/// Keeps it `!Send` like the one in `std`, so turning on the `std`
+	/// feature can't break code that compiled without it.
+	#[cfg(not(feature = "std"))]
+	_not_send: PhantomData<*const ()>,
+}
+
+#[cfg(not(feature = "std"))]
+unsafe impl<T: ?Sized + Sync> Sync for RwLockReadGuard<'_, T> {}
+#[cfg(not(feature = "std"))]
+unsafe impl<T: ?Sized + Sync> Sync for RwLockWriteGuard<'_, T> {}
+
+impl<T> RwLock<T> {
+	/// Creates an unlocked lock.
+	#[inline]
+	pub const fn new(value: T) -> Self {
+		Self {
+			#[cfg(feature = "std")]
+			inner: std::sync::RwLock::new(value),
+			#[cfg(not(feature = "std"))]
+			state: AtomicUsize::new(0),
+			#[cfg(not(feature = "std"))]
+			value: UnsafeCell::new(value),
+		}
+	}
+
+	/// Takes the value out of the lock.
+	#[inline]
+	pub fn into_inner(self) -> T {
+		#[cfg(feature = "std")]
+		return self
+			.inner
+			.into_inner()
+			.unwrap_or_else(std::sync::PoisonError::into_inner);
+
+		#[cfg(not(feature = "std"))]
+		self.value.into_inner()
+	}
+}
+
+impl<T: ?Sized> RwLock<T> {
+	/// Waits until no writer holds the lock, then takes it for reading.
+	/// Any number of readers can hold it at once.
+	#[inline]
+	pub fn read(&self) -> RwLockReadGuard<'_, T> {
+		#[cfg(feature = "std")]
+		return RwLockReadGuard {
+			inner: self
+				.inner
+				.read()
+				.unwrap_or_else(std::sync::PoisonError::into_inner),
+		};
+
+		#[cfg(not(feature = "std"))]
+		{
+			while !self.try_acquire_read() {
+				core::hint::spin_loop();
+			}
+
+			self.read_guard()
+		}
+	}
+
+	/// Waits until nobody holds the lock, then takes it for writing.
+	#[inline]
+	pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+		#[cfg(feature = "std")]
+		return RwLockWriteGuard {
+			inner: self
+				.inner
+				.write()
+				.unwrap_or_else(std::sync::PoisonError::into_inner),
+		};
+
+		#[cfg(not(feature = "std"))]
+		{
+			while !self.try_acquire_write() {
+				core::hint::spin_loop();
+			}
+
+			self.write_guard()
+		}
+	}
+
+	/// Takes the lock for reading if no writer holds it, without waiting.
+	#[inline]
+	fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
+		#[cfg(feature = "std")]
+		return match self.inner.try_read() {
+			Ok(inner) => Some(RwLockReadGuard { inner }),
+			Err(std::sync::TryLockError::Poisoned(err)) => Some(RwLockReadGuard {
+				inner: err.into_inner(),
+			}),
+			Err(std::sync::TryLockError::WouldBlock) => None,
+		};
+
+		#[cfg(not(feature = "std"))]
+		self.try_acquire_read().then(|| self.read_guard())
+	}
+}
+
+#[cfg(not(feature = "std"))]
+impl<T: ?Sized> RwLock<T> {
+	/// The guard for a read lock we just took.
+	#[inline]
+	fn read_guard(&self) -> RwLockReadGuard<'_, T> {
+		RwLockReadGuard {
+			lock: self,
+			_not_send: PhantomData,
+		}
+	}
+
+	/// The guard for the write lock we just took.
+	#[inline]
+	fn write_guard(&self) -> RwLockWriteGuard<'_, T> {
+		RwLockWriteGuard {
+			lock: self,
+			_not_send: PhantomData,
+		}
+	}
+
+	/// Adds a reader if no writer holds the lock.
+	#[inline]
+	#[cfg(not(feature = "critical-section"))]
+	fn try_acquire_read(&self) -> bool {
+		let state = self.state.load(Ordering::Relaxed);
+
+		state & WRITER == 0
+			&& self
+				.state
+				.compare_exchange_weak(state, state + 1, Ordering::Acquire, Ordering::Relaxed)
+				.is_ok()
+	}
+
+	/// Takes the lock for writing if nobody holds it.
+	#[inline]
+	#[cfg(not(feature = "critical-section"))]
+	fn try_acquire_write(&self) -> bool {
+		self.state
+			.compare_exchange_weak(0, WRITER, Ordering::Acquire, Ordering::Relaxed)
+			.is_ok()
+	}
+
+	/// Removes a reader.
+	#[inline]
+	#[cfg(not(feature = "critical-section"))]
+	fn unlock_read(&self) {
+		self.state.fetch_sub(1, Ordering::Release);
+	}
+
+	// Targets without CAS (or `fetch_sub`) do the same in critical sections.
+
+	#[cfg(feature = "critical-section")]
+	fn try_acquire_read(&self) -> bool {
+		critical_section::with(|_| {
+			let state = self.state.load(Ordering::Acquire);
+
+			if state & WRITER != 0 {
+				return false;
+			}
+
+			self.state.store(state + 1, Ordering::Relaxed);
+			true
+		})
+	}
+
+	#[cfg(feature = "critical-section")]
+	fn try_acquire_write(&self) -> bool {
+		critical_section::with(|_| {
+			if self.state.load(Ordering::Acquire) != 0 {
+				return false;
+			}
+
+			self.state.store(WRITER, Ordering::Relaxed);
+			true
+		})
+	}
+
+	#[cfg(feature = "critical-section")]
+	fn unlock_read(&self) {
+		critical_section::with(|_| {
+			let state = self.state.load(Ordering::Relaxed);
+			self.state.store(state - 1, Ordering::Release);
+		});
+	}
+}
+
+impl<T: Default> Default for RwLock<T> {
+	fn default() -> Self {
+		Self::new(T::default())
+	}
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLock<T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		// Reading would never finish if this thread holds the write lock.
+		match self.try_read() {
+			Some(guard) => f.debug_struct("RwLock").field("value", &&*guard).finish(),
+			None => f
+				.debug_struct("RwLock")
+				.field("value", &format_args!("<locked>"))
+				.finish(),
+		}
+	}
+}
+
+impl<T: ?Sized> Deref for RwLockReadGuard<'_, T> {
+	type Target = T;
+
+	#[inline]
+	fn deref(&self) -> &T {
+		#[cfg(feature = "std")]
+		return &self.inner;
+
+		// SAFETY: we hold a read lock, so nobody is writing.
+		#[cfg(not(feature = "std"))]
+		unsafe {
+			&*self.lock.value.get()
+		}
+	}
+}
+
+impl<T: ?Sized> Deref for RwLockWriteGuard<'_, T> {
+	type Target = T;
+
+	#[inline]
+	fn deref(&self) -> &T {
+		#[cfg(feature = "std")]
+		return &self.inner;
+
+		// SAFETY: we hold the write lock.
+		#[cfg(not(feature = "std"))]
+		unsafe {
+			&*self.lock.value.get()
+		}
+	}
+}
+
+impl<T: ?Sized> DerefMut for RwLockWriteGuard<'_, T> {
+	#[inline]
+	fn deref_mut(&mut self) -> &mut T {
+		#[cfg(feature = "std")]
+		return &mut self.inner;
+
+		// SAFETY: we hold the write lock.
+		#[cfg(not(feature = "std"))]
+		unsafe {
+			&mut *self.lock.value.get()
+		}
+	}
+}
+
+#[cfg(not(feature = "std"))]
+impl<T: ?Sized> Drop for RwLockReadGuard<'_, T> {
+	#[inline]
+	fn drop(&mut self) {
+		self.lock.unlock_read();
+	}
+}
+
+#[cfg(not(feature = "std"))]
+impl<T: ?Sized> Drop for RwLockWriteGuard<'_, T> {
+	#[inline]
+	fn drop(&mut self) {
+		self.lock.state.store(0, Ordering::Release);
+	}
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLockReadGuard<'_, T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		(**self).fmt(f)
+	}
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLockWriteGuard<'_, T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		(**self).fmt(f)
+	}
+}
@@ -0,0 +1,201 @@
+//! Reloading lazy statics when the process gets a `SIGHUP`.
+//!
+//! ```no_run
+//! use slazy::{slazy, sighup};
+//!
+//! slazy! {
+//!     pub reload CONFIG: String = std::fs::read_to_string("app.toml").unwrap();
+//! }
+//!
+//! sighup::reload_on_sighup(&[&CONFIG], |name, result| match result {
+//!     Ok(()) => eprintln!("reloaded {name}"),
+//!     Err(_) => eprintln!("failed to reload {name}, keeping the old value"),
+//! })
+//! .unwrap();
+//! ```
+//!
+//! Only available on Unix, with the `sighup` feature.
+
+use core::sync::atomic::{AtomicI32, Ordering};
+use std::boxed::Box;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+use std::vec::Vec;
+
+use crate::reload::Reload;
+
+/// Called after every static is reloaded, with its name and either `Ok(())`
+/// or what its initializer panicked with.
+type Report = Box<dyn Fn(&'static str, thread::Result<()>) + Send>;
+
+struct Registration {
+	statics: Vec<&'static dyn Reload>,
+	report: Report,
+}
+
+static REGISTRATIONS: Mutex<Vec<Registration>> = Mutex::new(Vec::new());
+
+/// The end of the pipe the signal handler writes to, or -1 before the first
+/// registration.
+static PIPE: AtomicI32 = AtomicI32::new(-1);
+
+/// Runs the initializers of `statics` again (in order) every time the process
+/// gets a `SIGHUP`, and calls `report` with the outcome of each one.
+///
+/// The reloads happen on a background thread. The handler is installed the
+/// first time this is called, and later calls add more statics to it.
+///
+/// If an initializer panics, the static keeps its old value.
+///
+/// # Errors
+///
+/// If the pipe or the signal handler can't be set up.
+pub fn reload_on_sighup(
+	statics: &[&'static dyn Reload],
+	report: impl Fn(&'static str, thread::Result<()>) + Send + 'static,
+) -> io::Result<()> {
+	let mut registrations = REGISTRATIONS.lock().unwrap_or_else(PoisonError::into_inner);
+
+	if PIPE.load(Ordering::Relaxed) == -1 {
+		install()?;
+	}
+
+	registrations.push(Registration {
+		statics: statics.to_vec(),
+		report: Box::new(report),
+	});
+
+	Ok(())
+}
+
+/// Creates the pipe, installs the signal handler and spawns the thread that
+/// does the reloading.
+fn install() -> io::Result<()> {
+	let mut fds = [0; 2];
+
+	// SAFETY: `fds` has room for both ends.
+	if unsafe { libc::pipe(fds.as_mut_ptr()) } == -1 {
+		return Err(io::Error::last_os_error());
+	}
+
+	let [read, write] = fds;
+
+	for fd in fds {
+		// SAFETY: `fd` was just opened.
+		unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+	}
+
+	// A burst of signals can fill the pipe. Dropping the extra bytes is fine,
+	// one reload covers all of them.
+	// SAFETY: `write` was just opened.
+	unsafe { libc::fcntl(write, libc::F_SETFL, libc::O_NONBLOCK) };
+
+	if let Err(err) = thread::Builder::new()
+		.name("slazy-sighup".into())
+		.spawn(move || listen(read))
+	{
+		close(read);
+		close(write);
+		return Err(err);
+	}
+
+	// Before the handler is installed, so a signal right after that isn't
+	// written to an invalid fd and lost.
+	PIPE.store(write, Ordering::Relaxed);
+
+	// SAFETY: an all-zero `sigaction` is valid, and `on_signal` only calls
+	// `write`, which is async-signal-safe.
+	unsafe {
+		let mut action: libc::sigaction = core::mem::zeroed();
+		action.sa_sigaction = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+		action.sa_flags = libc::SA_RESTART;
+		libc::sigemptyset(&mut action.sa_mask);
+
+		if libc::sigaction(libc::SIGHUP, &action, core::ptr::null_mut()) == -1 {
+			let err = io::Error::last_os_error();
+			// So the next call tries again. The listener sees the end of the
+			// pipe and exits.
+			PIPE.store(-1, Ordering::Relaxed);
+			close(write);
+			return Err(err);
+		}
+	}
+
+	Ok(())
+}
+
+fn close(fd: libc::c_int) {
+	// SAFETY: `fd` is open and nothing else uses it.
+	unsafe { libc::close(fd) };
+}
+
+extern "C" fn on_signal(_: libc::c_int) {
+	// `write` can set `errno` (to `EAGAIN` if the pipe is full), and the code
+	// the signal interrupted might be about to read it.
+	// SAFETY: `errno` always points to the current thread's `errno`.
+	let saved = unsafe { *errno() };
+
+	let byte = 1u8;
+	// SAFETY: writing one byte from a valid buffer.
+	unsafe { libc::write(PIPE.load(Ordering::Relaxed), (&byte as *const u8).cast(), 1) };
+
+	// SAFETY: as above.
+	unsafe { *errno() = saved };
+}
+
+/// Where the current thread's `errno` lives.
+unsafe fn errno() -> *mut libc::c_int {
+	#[cfg(any(
+		target_os = "linux",
+		target_os = "emscripten",
+		target_os = "dragonfly",
+		target_os = "hurd",
+		target_os = "redox",
+		target_os = "l4re",
+	))]
+	return libc::__errno_location();
+
+	#[cfg(any(
+		target_os = "android",
+		target_os = "netbsd",
+		target_os = "openbsd",
+		target_os = "cygwin",
+		target_os = "nuttx",
+	))]
+	return libc::__errno();
+
+	#[cfg(any(target_vendor = "apple", target_os = "freebsd"))]
+	return libc::__error();
+
+	#[cfg(any(target_os = "solaris", target_os = "illumos"))]
+	return libc::___errno();
+}
+
+fn listen(pipe: libc::c_int) {
+	let mut buf = [0u8; 64];
+
+	loop {
+		// SAFETY: reading into a valid buffer of that size.
+		let read = unsafe { libc::read(pipe, buf.as_mut_ptr().cast(), buf.len()) };
+
+		if read > 0 {
+			reload_all();
+		} else if read == 0 || io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+			close(pipe);
+			return;
+		}
+	}
+}
+
+fn reload_all() {
+	let registrations = REGISTRATIONS.lock().unwrap_or_else(PoisonError::into_inner);
+
+	for registration in registrations.iter() {
+		for lazy in &registration.statics {
+			let result = panic::catch_unwind(AssertUnwindSafe(|| lazy.reload()));
+			(registration.report)(lazy.name(), result);
+		}
+	}
+}
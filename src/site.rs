@@ -0,0 +1,24 @@
+//! Where a lazy static was declared, for error messages.
+
+use core::fmt;
+
+/// The name of a lazy static and where its `slazy!` block is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Site {
+	/// The static's name.
+	pub name: &'static str,
+	/// The file with the `slazy!` block.
+	pub file: &'static str,
+	/// The line of the `slazy!` block.
+	pub line: u32,
+}
+
+impl fmt::Display for Site {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"`{}` (declared at {}:{})",
+			self.name, self.file, self.line
+		)
+	}
+}
@@ -0,0 +1,126 @@
+//! Helpers for tests that need lazy statics to start over.
+//!
+//! With the `testing` feature, every lazy static gets an `unsafe fn reset()`
+//! that drops its value, so the next access runs the initializer again.
+//! [`fresh`] does that for a set of statics, before and after a closure:
+//!
+//! ```
+//! use slazy::{slazy, testing};
+//! use std::sync::atomic::{AtomicU32, Ordering};
+//!
+//! static CALLS: AtomicU32 = AtomicU32::new(0);
+//!
+//! slazy! {
+//!     pub COUNT: u32 = CALLS.fetch_add(1, Ordering::Relaxed);
+//! }
+//!
+//! assert_eq!(*COUNT, 0);
+//!
+//! // SAFETY: no references to `COUNT`'s value are alive.
+//! unsafe { testing::fresh(&[&COUNT], || assert_eq!(*COUNT, 1)) };
+//!
+//! assert_eq!(*COUNT, 2);
+//! ```
+//!
+//! Statics declared with `slazy_thread_local!` can't be reset.
+//!
+//! Since tests run in parallel, plain statics also get
+//! `with_override(value, || ...)`, which only changes what the static gives
+//! on the current thread:
+//!
+//! ```
+//! use slazy::slazy;
+//!
+//! slazy! {
+//!     pub URL: &'static str = "https://example.com";
+//! }
+//!
+//! URL::with_override("http://localhost:8080", || {
+//!     assert_eq!(*URL, "http://localhost:8080");
+//!     std::thread::spawn(|| assert_eq!(*URL, "https://example.com"))
+//!         .join()
+//!         .unwrap();
+//! });
+//!
+//! assert_eq!(*URL, "https://example.com");
+//! ```
+//!
+//! Only available with the `testing` feature.
+
+use core::cell::RefCell;
+use std::thread_local;
+use std::vec::Vec;
+
+thread_local! {
+	/// The current thread's overrides, as `(cell, value)`, innermost last.
+	static OVERRIDES: RefCell<Vec<(*const (), *const ())>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Makes `cell`'s static give `value` on the current thread while `f` runs.
+#[doc(hidden)]
+pub fn __with_override<T: 'static, C, R>(cell: &'static C, value: T, f: impl FnOnce() -> R) -> R {
+	struct Pop;
+
+	impl Drop for Pop {
+		fn drop(&mut self) {
+			OVERRIDES.with(|overrides| overrides.borrow_mut().pop());
+		}
+	}
+
+	// Leaked so the references handed out while `f` runs stay valid after it.
+	let value: *const T = crate::__leak(value);
+	let cell = cell as *const C as *const ();
+
+	OVERRIDES.with(|overrides| overrides.borrow_mut().push((cell, value.cast())));
+	let _pop = Pop;
+	f()
+}
+
+/// The value `cell`'s static is overridden with on the current thread.
+#[inline]
+pub(crate) fn get<T: 'static>(cell: *const ()) -> Option<&'static T> {
+	OVERRIDES.with(|overrides| {
+		let overrides = overrides.borrow();
+		let (_, value) = overrides.iter().rev().find(|(key, _)| *key == cell)?;
+		// SAFETY: every cell belongs to one static, whose overrides are all
+		// leaked `T`s.
+		Some(unsafe { &*value.cast::<T>() })
+	})
+}
+
+/// A lazy static that can be reset, seen from code that doesn't know its
+/// type (like [`fresh`]).
+///
+/// Implemented by every lazy static with the `testing` feature.
+pub trait Reset {
+	/// Drops the value, so the next access runs the initializer again.
+	///
+	/// # Safety
+	///
+	/// No references to the value can be alive, and nobody can be using it at
+	/// the same time.
+	unsafe fn reset(&self);
+}
+
+/// Resets `statics`, runs `f`, and resets them again, even if `f` panics.
+///
+/// # Safety
+///
+/// No references to the values of `statics` can be alive before or after
+/// `f`, and nobody else can be using them while they are reset.
+pub unsafe fn fresh<R>(statics: &[&dyn Reset], f: impl FnOnce() -> R) -> R {
+	struct ResetOnDrop<'a>(&'a [&'a dyn Reset]);
+
+	impl Drop for ResetOnDrop<'_> {
+		fn drop(&mut self) {
+			for lazy in self.0 {
+				// SAFETY: guaranteed by the caller of `fresh`.
+				unsafe { lazy.reset() }
+			}
+		}
+	}
+
+	drop(ResetOnDrop(statics));
+	let _after = ResetOnDrop(statics);
+	f()
+}
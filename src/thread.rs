@@ -0,0 +1,12 @@
+//! Telling threads apart without `ThreadId::as_u64`.
+
+/// A number that is unique to the current thread for as long as it lives,
+/// and never zero.
+#[inline]
+pub(crate) fn current() -> usize {
+	std::thread_local! {
+		static TOKEN: u8 = const { 0 };
+	}
+
+	TOKEN.with(|token| token as *const u8 as usize)
+}
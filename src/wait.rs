@@ -0,0 +1,249 @@
+//! How threads wait for someone else's initializer to finish.
+//!
+//! Every lazy static picks a [`WaitStrategy`], which is used whenever a thread
+//! (or interrupt, or task) needs the value while another one is still running
+//! the initializer. It defaults to [`DefaultWait`], but can be changed per
+//! static:
+//!
+//! ```
+//! use slazy::{slazy, wait::Backoff};
+//!
+//! slazy! {
+//!     #[wait(Backoff)]
+//!     pub TABLE: [u8; 256] = core::array::from_fn(|i| i as u8);
+//! }
+//!
+//! assert_eq!(TABLE[42], 42);
+//! ```
+
+use core::sync::atomic::{AtomicPtr, Ordering};
+#[cfg(feature = "std")]
+use core::time::Duration;
+
+#[cfg(feature = "std")]
+use crate::{cell, Site};
+
+/// Decides what a thread does while another one runs an initializer.
+///
+/// You can implement it yourself to plug in whatever your platform offers:
+///
+/// ```
+/// use slazy::{slazy, wait::WaitStrategy};
+///
+/// struct Sleep;
+///
+/// impl WaitStrategy for Sleep {
+///     fn wait_until(done: impl Fn() -> bool) {
+///         while !done() {
+///             std::thread::sleep(std::time::Duration::from_millis(1));
+///         }
+///     }
+/// }
+///
+/// slazy! {
+///     #[wait(Sleep)]
+///     FOO: u32 = 42;
+/// }
+///
+/// assert_eq!(*FOO, 42);
+/// ```
+pub trait WaitStrategy {
+	/// Blocks until `done` returns `true`.
+	fn wait_until(done: impl Fn() -> bool);
+}
+
+/// The strategy used when a lazy static doesn't ask for one.
+///
+/// [`Park`] with the `std` feature, [`Spin`] without it.
+#[cfg(feature = "std")]
+pub type DefaultWait = Park;
+
+/// The strategy used when a lazy static doesn't ask for one.
+///
+/// `Park` with the `std` feature, [`Spin`] without it.
+#[cfg(not(feature = "std"))]
+pub type DefaultWait = Spin;
+
+/// Checks again and again, as fast as possible.
+pub struct Spin;
+
+impl WaitStrategy for Spin {
+	#[inline]
+	fn wait_until(done: impl Fn() -> bool) {
+		while !done() {
+			core::hint::spin_loop();
+		}
+	}
+}
+
+/// Spins, but doubles the time between checks (up to a limit) every time
+/// the initializer still isn't done.
+pub struct Backoff;
+
+impl Backoff {
+	const MAX_SPINS: u32 = 1 << 10;
+}
+
+impl WaitStrategy for Backoff {
+	#[inline]
+	fn wait_until(done: impl Fn() -> bool) {
+		let mut spins = 1;
+
+		while !done() {
+			for _ in 0..spins {
+				core::hint::spin_loop();
+			}
+
+			spins = (spins * 2).min(Self::MAX_SPINS);
+		}
+	}
+}
+
+/// Calls the yield hook (see [`set_yield_hook`]) between checks, so the
+/// scheduler can run something else.
+pub struct Yield;
+
+impl WaitStrategy for Yield {
+	#[inline]
+	fn wait_until(done: impl Fn() -> bool) {
+		while !done() {
+			yield_now();
+		}
+	}
+}
+
+static YIELD_HOOK: AtomicPtr<()> = AtomicPtr::new(core::ptr::null_mut());
+
+/// Sets the function [`Yield`] calls between checks, like your RTOS's
+/// `task_yield`.
+///
+/// Until this is called, it's `std::thread::yield_now` with the `std`
+/// feature, and [`core::hint::spin_loop`] without it.
+pub fn set_yield_hook(hook: fn()) {
+	YIELD_HOOK.store(hook as *mut (), Ordering::Release);
+}
+
+fn yield_now() {
+	let hook = YIELD_HOOK.load(Ordering::Acquire);
+
+	if hook.is_null() {
+		#[cfg(feature = "std")]
+		std::thread::yield_now();
+
+		#[cfg(not(feature = "std"))]
+		core::hint::spin_loop();
+	} else {
+		// SAFETY: the only non-null value ever stored is a `fn()`.
+		let hook = unsafe { core::mem::transmute::<*mut (), fn()>(hook) };
+		hook();
+	}
+}
+
+/// Parks the thread until the initializer is done.
+///
+/// Only available with the `std` feature.
+#[cfg(feature = "std")]
+pub struct Park;
+
+#[cfg(feature = "std")]
+impl WaitStrategy for Park {
+	#[inline]
+	fn wait_until(done: impl Fn() -> bool) {
+		park::wait_until(done);
+	}
+}
+
+#[cfg(feature = "std")]
+impl Park {
+	/// Like [`wait_until`](WaitStrategy::wait_until), but gives up after
+	/// `timeout`. Returns whether `done` returned `true`.
+	pub fn wait_timeout(done: impl Fn() -> bool, timeout: Duration) -> bool {
+		park::wait_timeout(done, timeout)
+	}
+}
+
+/// Waits for a lazy static to be initialized by someone else, for at most
+/// `timeout` if there is one. Returns whether it was.
+///
+/// # Panics
+///
+/// If the static is or becomes poisoned.
+#[cfg(feature = "std")]
+#[doc(hidden)]
+pub fn __wait_for_init(
+	site: &'static Site,
+	timeout: Option<Duration>,
+	initialized: impl Fn() -> bool,
+	poisoned: impl Fn() -> bool,
+) -> bool {
+	let done = || initialized() || poisoned();
+
+	match timeout {
+		Some(timeout) => _ = Park::wait_timeout(done, timeout),
+		None => Park::wait_until(done),
+	}
+
+	if poisoned() {
+		cell::poisoned(site);
+	}
+
+	initialized()
+}
+
+/// Wakes up every thread blocked in [`Park`] so it can check again.
+#[inline]
+pub(crate) fn notify_all() {
+	#[cfg(feature = "std")]
+	park::notify_all();
+}
+
+#[cfg(feature = "std")]
+mod park {
+	use std::sync::{Condvar, Mutex, PoisonError};
+	use std::time::{Duration, Instant};
+
+	// A single queue is shared by all lazies. Initializers finish rarely, so
+	// waking a few threads that were waiting on something else is cheap.
+	static LOCK: Mutex<()> = Mutex::new(());
+	static CONDVAR: Condvar = Condvar::new();
+
+	pub(super) fn wait_until(done: impl Fn() -> bool) {
+		let mut guard = LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+
+		while !done() {
+			guard = CONDVAR.wait(guard).unwrap_or_else(PoisonError::into_inner);
+		}
+	}
+
+	pub(super) fn wait_timeout(done: impl Fn() -> bool, timeout: Duration) -> bool {
+		// A timeout too long to represent is as good as none.
+		let Some(deadline) = Instant::now().checked_add(timeout) else {
+			wait_until(&done);
+			return true;
+		};
+
+		let mut guard = LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+
+		while !done() {
+			let now = Instant::now();
+
+			if now >= deadline {
+				return false;
+			}
+
+			guard = CONDVAR
+				.wait_timeout(guard, deadline - now)
+				.unwrap_or_else(PoisonError::into_inner)
+				.0;
+		}
+
+		true
+	}
+
+	pub(super) fn notify_all() {
+		// Taking the lock makes sure nobody is between checking `done` and
+		// going to sleep, so the wakeup can't get lost.
+		drop(LOCK.lock().unwrap_or_else(PoisonError::into_inner));
+		CONDVAR.notify_all();
+	}
+}
@@ -0,0 +1,35 @@
+#![cfg(feature = "critical-section")]
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use slazy::slazy;
+
+static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+slazy! {
+	COUNTED: u32 = {
+		RUNS.fetch_add(1, Ordering::SeqCst);
+		42
+	};
+}
+
+#[test]
+fn initializes_once_across_threads() {
+	let handles: Vec<_> = (0..8).map(|_| thread::spawn(|| *COUNTED)).collect();
+
+	for handle in handles {
+		assert_eq!(handle.join().unwrap(), 42);
+	}
+
+	assert_eq!(RUNS.load(Ordering::SeqCst), 1);
+}
+
+slazy! {
+	NESTED: u32 = critical_section::with(|_| 7);
+}
+
+#[test]
+fn initializer_can_enter_a_critical_section() {
+	assert_eq!(*NESTED, 7);
+}
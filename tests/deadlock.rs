@@ -0,0 +1,50 @@
+#![cfg(all(feature = "debug-deadlock", not(feature = "critical-section")))]
+
+use std::panic;
+use std::sync::Barrier;
+use std::thread;
+
+use slazy::slazy;
+
+static BOTH_RUNNING: Barrier = Barrier::new(2);
+
+slazy! {
+	A: u32 = {
+		BOTH_RUNNING.wait();
+		*B + 1
+	};
+
+	B: u32 = {
+		BOTH_RUNNING.wait();
+		*A + 1
+	};
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+	match payload.downcast::<String>() {
+		Ok(message) => *message,
+		Err(payload) => payload.downcast::<&str>().unwrap().to_string(),
+	}
+}
+
+#[test]
+fn cycle_across_threads_panics() {
+	panic::set_hook(Box::new(|_| {}));
+
+	let a = thread::spawn(|| *A);
+	let b = thread::spawn(|| *B);
+	let messages = [a.join(), b.join()].map(|result| panic_message(result.unwrap_err()));
+
+	_ = panic::take_hook();
+
+	// One thread notices the cycle. Its panic poisons the static it was
+	// initializing, which wakes up the other one.
+	assert!(
+		messages
+			.iter()
+			.any(|message| message.contains("deadlock: `A` -> `B` -> `A`")
+				|| message.contains("deadlock: `B` -> `A` -> `B`")),
+		"{messages:?}"
+	);
+	assert!(A.is_poisoned() || B.is_poisoned());
+}
@@ -0,0 +1,45 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Barrier;
+use std::thread;
+use std::time::Duration;
+
+use slazy::slazy;
+
+const THREADS: usize = 16;
+
+static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+slazy! {
+	COUNTED: Vec<u32> = {
+		RUNS.fetch_add(1, Ordering::SeqCst);
+		// Give the other threads time to pile up on it.
+		thread::sleep(Duration::from_millis(20));
+		vec![1, 2, 3]
+	};
+}
+
+#[test]
+fn initializes_once_under_contention() {
+	let barrier = Barrier::new(THREADS);
+
+	let addresses: Vec<usize> = thread::scope(|scope| {
+		let handles: Vec<_> = (0..THREADS)
+			.map(|_| {
+				scope.spawn(|| {
+					barrier.wait();
+					assert_eq!(*COUNTED, [1, 2, 3]);
+					COUNTED.as_ptr() as usize
+				})
+			})
+			.collect();
+
+		handles
+			.into_iter()
+			.map(|handle| handle.join().unwrap())
+			.collect()
+	});
+
+	assert_eq!(RUNS.load(Ordering::SeqCst), 1);
+	// Everyone got the same value, not just an equal one.
+	assert!(addresses.iter().all(|&address| address == addresses[0]));
+}
@@ -0,0 +1,46 @@
+#![cfg(any(feature = "std", feature = "critical-section"))]
+
+use std::panic;
+
+use slazy::slazy;
+
+slazy! {
+	SELF: u32 = *SELF + 1;
+
+	FOO: u32 = *BAR + 1;
+	BAR: u32 = *FOO + 1;
+}
+
+fn panic_message(f: impl FnOnce() + panic::UnwindSafe) -> String {
+	let payload = panic::catch_unwind(f).unwrap_err();
+
+	match payload.downcast::<String>() {
+		Ok(message) => *message,
+		Err(payload) => payload.downcast::<&str>().unwrap().to_string(),
+	}
+}
+
+#[test]
+fn direct_recursion_panics() {
+	let message = panic_message(|| _ = *SELF);
+
+	assert!(
+		message.starts_with(
+			"slazy: recursive initialization of `SELF` (declared at tests/recursion.rs:"
+		),
+		"{message}"
+	);
+}
+
+#[test]
+fn recursion_through_another_lazy_panics() {
+	let message = panic_message(|| _ = *FOO);
+
+	assert!(
+		message.contains("recursive initialization of `FOO`"),
+		"{message}"
+	);
+	// Both initializers unwound, so both are poisoned.
+	assert!(FOO.is_poisoned());
+	assert!(BAR.is_poisoned());
+}
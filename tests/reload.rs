@@ -0,0 +1,86 @@
+#![cfg(feature = "std")]
+
+use std::sync::atomic::{AtomicBool, AtomicIsize, Ordering};
+use std::thread;
+
+use slazy::slazy;
+
+/// How many `Value`s haven't been dropped yet.
+static LIVE: AtomicIsize = AtomicIsize::new(0);
+
+/// A value that notices if it's read after being freed: every element is
+/// the same number, and dropping it clears them.
+struct Value(Vec<usize>);
+
+impl Value {
+	fn new(n: usize) -> Self {
+		LIVE.fetch_add(1, Ordering::SeqCst);
+		Self(vec![n; 16])
+	}
+
+	fn check(&self) -> usize {
+		let n = self.0[0];
+		assert!(self.0.iter().all(|&x| x == n), "torn value: {:?}", self.0);
+		n
+	}
+}
+
+impl Drop for Value {
+	fn drop(&mut self) {
+		self.0.fill(usize::MAX);
+		LIVE.fetch_sub(1, Ordering::SeqCst);
+	}
+}
+
+slazy! {
+	reload CURRENT: Value = Value::new(0);
+	DOUBLED: usize = derive(CURRENT => |value| value.check() * 2);
+}
+
+#[test]
+fn concurrent_stores_and_loads() {
+	const WRITERS: usize = 4;
+	const READERS: usize = 4;
+	const STORES: usize = 20_000;
+
+	let done = AtomicBool::new(false);
+
+	thread::scope(|scope| {
+		let readers: Vec<_> = (0..READERS)
+			.map(|_| {
+				scope.spawn(|| {
+					while !done.load(Ordering::SeqCst) {
+						let value = CURRENT.load();
+						value.check();
+						assert_eq!(*DOUBLED.load() % 2, 0);
+						drop(value);
+					}
+				})
+			})
+			.collect();
+
+		let writers: Vec<_> = (0..WRITERS)
+			.map(|writer| {
+				scope.spawn(move || {
+					for i in 0..STORES {
+						CURRENT.store(Value::new(writer * STORES + i));
+					}
+				})
+			})
+			.collect();
+
+		for writer in writers {
+			writer.join().unwrap();
+		}
+
+		done.store(true, Ordering::SeqCst);
+
+		for reader in readers {
+			reader.join().unwrap();
+		}
+	});
+
+	assert_eq!(CURRENT.generation(), WRITERS * STORES);
+	// Only the current value is left.
+	assert_eq!(LIVE.load(Ordering::SeqCst), 1);
+}
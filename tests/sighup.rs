@@ -0,0 +1,45 @@
+#![cfg(all(feature = "sighup", unix))]
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use slazy::{sighup, slazy};
+
+static LOADS: AtomicU32 = AtomicU32::new(0);
+
+slazy! {
+	reload CONFIG: u32 = LOADS.fetch_add(1, Ordering::SeqCst);
+
+	reload FLAKY: u32 = {
+		static CALLS: AtomicU32 = AtomicU32::new(0);
+		let call = CALLS.fetch_add(1, Ordering::SeqCst);
+		assert_eq!(call, 0, "only works the first time");
+		call
+	};
+}
+
+#[test]
+fn sighup_reloads_and_reports() {
+	assert_eq!(*CONFIG.load(), 0);
+	assert_eq!(*FLAKY.load(), 0);
+
+	let (tx, rx) = mpsc::channel();
+	let tx = std::sync::Mutex::new(tx);
+
+	sighup::reload_on_sighup(&[&CONFIG, &FLAKY], move |name, result| {
+		tx.lock().unwrap().send((name, result.is_ok())).unwrap();
+	})
+	.unwrap();
+
+	// SAFETY: raising a signal we have a handler for.
+	assert_eq!(unsafe { libc::raise(libc::SIGHUP) }, 0);
+
+	let timeout = Duration::from_secs(10);
+	assert_eq!(rx.recv_timeout(timeout).unwrap(), ("CONFIG", true));
+	assert_eq!(rx.recv_timeout(timeout).unwrap(), ("FLAKY", false));
+
+	assert_eq!(*CONFIG.load(), 1);
+	// The failed reload kept the old value.
+	assert_eq!(*FLAKY.load(), 0);
+}
@@ -0,0 +1,76 @@
+#![cfg(feature = "testing")]
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use slazy::{slazy, testing};
+
+static CALLS: AtomicU32 = AtomicU32::new(0);
+
+slazy! {
+	COUNT: u32 = CALLS.fetch_add(1, Ordering::SeqCst);
+
+	FAILS: Result<u32, &'static str> = try Err("nope");
+
+	mut LIST: Vec<u32> = vec![1];
+
+	POISONED: u32 = panic!("boom");
+
+	NAME: String = String::from("real");
+}
+
+#[test]
+fn reset_runs_the_initializer_again() {
+	let first = *COUNT;
+	// SAFETY: no references to the value are alive.
+	unsafe { COUNT.reset() };
+	assert_eq!(*COUNT, first + 1);
+
+	LIST.lock().push(2);
+	// SAFETY: the guard was dropped.
+	unsafe { LIST.reset() };
+	assert_eq!(*LIST.lock(), [1]);
+}
+
+#[test]
+fn reset_clears_the_poison() {
+	assert!(std::panic::catch_unwind(|| *POISONED).is_err());
+	assert!(POISONED.is_poisoned());
+
+	// SAFETY: the initializer never finished, so there is no value.
+	unsafe { POISONED.reset() };
+	assert!(!POISONED.is_poisoned());
+}
+
+#[test]
+fn fresh_resets_before_and_after() {
+	assert!(FAILS.get().is_err());
+
+	// SAFETY: no references to the value are alive.
+	unsafe { testing::fresh(&[&FAILS], || assert!(FAILS.get().is_err())) };
+}
+
+#[test]
+fn overrides_are_per_thread_and_nest() {
+	NAME::with_override(String::from("outer"), || {
+		assert_eq!(*NAME, "outer");
+
+		NAME::with_override(String::from("inner"), || assert_eq!(*NAME, "inner"));
+		assert_eq!(*NAME, "outer");
+
+		std::thread::spawn(|| assert_eq!(*NAME, "real"))
+			.join()
+			.unwrap();
+	});
+
+	assert_eq!(*NAME, "real");
+}
+
+#[test]
+fn overrides_end_when_the_closure_panics() {
+	let result = std::panic::catch_unwind(|| {
+		NAME::with_override(String::from("fake"), || panic!("test failed"))
+	});
+
+	assert!(result.is_err());
+	assert_eq!(*NAME, "real");
+}
@@ -0,0 +1,8 @@
+//! Checks the compile errors `slazy!` gives for malformed declarations.
+
+#[test]
+fn ui() {
+	let t = trybuild::TestCases::new();
+	t.pass("tests/ui/pass/*.rs");
+	t.compile_fail("tests/ui/fail/*.rs");
+}
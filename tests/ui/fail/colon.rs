@@ -0,0 +1,11 @@
+use slazy::slazy;
+
+slazy! {
+	FOO: u32: 1;
+}
+
+slazy! {
+	rw BAR: Vec<u8>: Vec::new();
+}
+
+fn main() {}
@@ -0,0 +1,14 @@
+#![deny(deprecated)]
+
+use slazy::slazy;
+
+#[deprecated(note = "use `new_port`")]
+fn old_port() -> u16 {
+	80
+}
+
+slazy! {
+	PORT: u16 = old_port();
+}
+
+fn main() {}
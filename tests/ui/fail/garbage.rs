@@ -0,0 +1,7 @@
+use slazy::slazy;
+
+slazy! {
+	1 + 1
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use slazy::slazy;
+
+slazy! {
+	FOO = 1;
+}
+
+slazy! {
+	mut BAR = Vec::<u8>::new();
+}
+
+fn main() {}
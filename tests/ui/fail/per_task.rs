@@ -0,0 +1,10 @@
+use slazy::slazy;
+
+struct Task;
+
+slazy! {
+	#[per_task(Task, 4)]
+	FOO: Result<u32, ()> = try Ok(1);
+}
+
+fn main() {}
@@ -0,0 +1,18 @@
+use slazy::{per_task::ThreadIdProvider, slazy};
+
+struct Task;
+
+unsafe impl ThreadIdProvider for Task {
+	fn current_id() -> usize {
+		0
+	}
+}
+
+slazy! {
+	#[per_task(Task, 4)]
+	FOO: u32 = 1;
+}
+
+fn main() {
+	FOO.wait();
+}
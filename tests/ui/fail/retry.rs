@@ -0,0 +1,8 @@
+use slazy::slazy;
+
+slazy! {
+	#[retry(3)]
+	FOO: u32 = 1;
+}
+
+fn main() {}
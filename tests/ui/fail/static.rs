@@ -0,0 +1,7 @@
+use slazy::slazy;
+
+slazy! {
+	pub static FOO: u32 = 1;
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+// Declaring a deprecated static doesn't warn, only using it does.
+#![deny(deprecated)]
+
+slazy::slazy! {
+	#[deprecated(note = "use `NEW`")]
+	OLD: u32 = 1;
+	NEW: u32 = 2;
+}
+
+fn main() {
+	assert_eq!(*NEW, 2);
+}
@@ -0,0 +1,21 @@
+// No `use`: the macro has to find itself through `$crate`.
+
+slazy::slazy! {
+	pub FOO: u32 = 1;
+	#[retry]
+	BAR: Result<u32, ()> = try Ok(*FOO + 1);
+	mut LIST: Vec<u32> = Vec::new();
+	BAZ: u32 = *FOO + 2
+}
+
+slazy::slazy! {
+	QUX: u32 = 4;
+}
+
+fn main() {
+	LIST.lock().push(*BAZ);
+	assert_eq!(*FOO, 1);
+	assert_eq!(BAR.get().ok(), Some(&2));
+	assert_eq!(*LIST.lock(), [3]);
+	assert_eq!(*QUX, 4);
+}
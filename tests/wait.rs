@@ -0,0 +1,52 @@
+#![cfg(feature = "std")]
+
+use std::sync::Barrier;
+use std::thread;
+use std::time::Duration;
+
+use slazy::slazy;
+
+slazy! {
+	SLOW: u32 = {
+		thread::sleep(Duration::from_millis(50));
+		42
+	};
+
+	BROKEN: u32 = {
+		thread::sleep(Duration::from_millis(50));
+		panic!("boom")
+	};
+
+	NEVER: u32 = unreachable!("nobody initializes it");
+}
+
+#[test]
+fn wait_returns_once_someone_else_initialized_it() {
+	let barrier = Barrier::new(2);
+
+	thread::scope(|scope| {
+		scope.spawn(|| {
+			barrier.wait();
+			assert_eq!(*SLOW, 42);
+		});
+
+		barrier.wait();
+		SLOW.wait();
+		assert_eq!(SLOW.try_get(), Some(&42));
+	});
+}
+
+#[test]
+fn wait_timeout_gives_up() {
+	assert!(!NEVER.wait_timeout(Duration::from_millis(20)));
+	assert!(!NEVER.is_initialized());
+}
+
+#[test]
+fn wait_panics_if_the_initializer_does() {
+	let initializer = thread::spawn(|| *BROKEN);
+	let waiter = thread::spawn(|| BROKEN.wait_timeout(Duration::from_secs(10)));
+
+	assert!(initializer.join().is_err());
+	assert!(waiter.join().is_err());
+}
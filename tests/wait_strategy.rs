@@ -0,0 +1,68 @@
+// With `critical-section`, nobody ever waits for an initializer.
+#![cfg(not(feature = "critical-section"))]
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Barrier;
+use std::thread;
+use std::time::Duration;
+
+use slazy::slazy;
+use slazy::wait::{set_yield_hook, Backoff, Yield};
+
+const THREADS: usize = 16;
+
+static BACKOFF_RUNS: AtomicUsize = AtomicUsize::new(0);
+static YIELD_RUNS: AtomicUsize = AtomicUsize::new(0);
+static HOOK_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+slazy! {
+	#[wait(Backoff)]
+	BACKOFF: u32 = {
+		BACKOFF_RUNS.fetch_add(1, Ordering::SeqCst);
+		thread::sleep(Duration::from_millis(20));
+		1
+	};
+
+	#[wait(Yield)]
+	YIELD: u32 = {
+		YIELD_RUNS.fetch_add(1, Ordering::SeqCst);
+		thread::sleep(Duration::from_millis(20));
+		2
+	};
+}
+
+/// Has `THREADS` threads read `value` at the same time.
+fn race(value: fn() -> u32) {
+	let barrier = Barrier::new(THREADS);
+
+	thread::scope(|scope| {
+		for _ in 0..THREADS {
+			scope.spawn(|| {
+				barrier.wait();
+				value()
+			});
+		}
+	});
+}
+
+#[test]
+fn backoff_waits_for_the_initializer() {
+	race(|| *BACKOFF);
+
+	assert_eq!(*BACKOFF, 1);
+	assert_eq!(BACKOFF_RUNS.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn yield_calls_the_hook_while_waiting() {
+	set_yield_hook(|| {
+		HOOK_CALLS.fetch_add(1, Ordering::SeqCst);
+		thread::yield_now();
+	});
+
+	race(|| *YIELD);
+
+	assert_eq!(*YIELD, 2);
+	assert_eq!(YIELD_RUNS.load(Ordering::SeqCst), 1);
+	assert!(HOOK_CALLS.load(Ordering::SeqCst) > 0);
+}